@@ -0,0 +1,245 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, Fields, Lit, LitStr, Type};
+
+//#[derive(AttributesReprCpacked)] for #[repr(C, packed)] structs, see gru_opengl::gl::AttributesReprCpacked
+//field attributes: #[attribute(name = "in_pos")] (defaults to the field name), #[attribute(normalized)] (integer fields only)
+#[proc_macro_derive(AttributesReprCpacked, attributes(attribute))]
+pub fn derive_attributes_repr_cpacked(input: TokenStream) -> TokenStream
+{
+	let input = parse_macro_input!(input as DeriveInput);
+	match attributes_repr_cpacked(&input)
+	{
+		Ok(tokens) => tokens.into(),
+		Err(err) => err.to_compile_error().into()
+	}
+}
+
+//#[derive(UniformStruct)] maps every field to the uniform "name.field", see gru_opengl::gl::UniformStruct
+//field attributes: #[uniform(name = "...")] (defaults to the field name), #[uniform(nested)] for fields that are UniformStructs themselves
+#[proc_macro_derive(UniformStruct, attributes(uniform))]
+pub fn derive_uniform_struct(input: TokenStream) -> TokenStream
+{
+	let input = parse_macro_input!(input as DeriveInput);
+	match uniform_struct(&input)
+	{
+		Ok(tokens) => tokens.into(),
+		Err(err) => err.to_compile_error().into()
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar
+{
+	F32,
+	I8,
+	U8,
+	I16,
+	U16,
+	I32,
+	U32
+}
+
+impl Scalar
+{
+	fn parse(ty: &Type) -> Option<Self>
+	{
+		let Type::Path(path) = ty else { return None; };
+		let ident = path.path.get_ident()?;
+		match ident.to_string().as_str()
+		{
+			"f32" => Some(Self::F32),
+			"i8" => Some(Self::I8),
+			"u8" => Some(Self::U8),
+			"i16" => Some(Self::I16),
+			"u16" => Some(Self::U16),
+			"i32" => Some(Self::I32),
+			"u32" => Some(Self::U32),
+			_ => None
+		}
+	}
+
+	fn bytes(&self) -> usize
+	{
+		match self
+		{
+			Self::I8 | Self::U8 => 1,
+			Self::I16 | Self::U16 => 2,
+			Self::F32 | Self::I32 | Self::U32 => 4
+		}
+	}
+
+	fn buffer_type(&self, size: u8, normalized: bool) -> TokenStream2
+	{
+		match self
+		{
+			Self::F32 => quote!(::gru_opengl::gl::BufferType::Float { size: #size }),
+			Self::I8 => quote!(::gru_opengl::gl::BufferType::Byte { signed: true, normalized: #normalized, size: #size }),
+			Self::U8 => quote!(::gru_opengl::gl::BufferType::Byte { signed: false, normalized: #normalized, size: #size }),
+			Self::I16 => quote!(::gru_opengl::gl::BufferType::Short { signed: true, normalized: #normalized, size: #size }),
+			Self::U16 => quote!(::gru_opengl::gl::BufferType::Short { signed: false, normalized: #normalized, size: #size }),
+			Self::I32 => quote!(::gru_opengl::gl::BufferType::Int { signed: true, size: #size }),
+			Self::U32 => quote!(::gru_opengl::gl::BufferType::Int { signed: false, size: #size })
+		}
+	}
+}
+
+//f32, [f32; N], (f32, f32) and Vec2/Vec3/Vec4 (likewise for the integer types)
+fn infer(ty: &Type) -> Option<(Scalar, u8)>
+{
+	let (scalar, size) = match ty
+	{
+		Type::Path(path) => match Scalar::parse(ty)
+		{
+			Some(scalar) => (scalar, 1),
+			None => match path.path.segments.last()?.ident.to_string().as_str()
+			{
+				"Vec2" => (Scalar::F32, 2),
+				"Vec3" => (Scalar::F32, 3),
+				"Vec4" => (Scalar::F32, 4),
+				_ => return None
+			}
+		},
+		Type::Array(array) =>
+		{
+			let Expr::Lit(len) = &array.len else { return None; };
+			let Lit::Int(len) = &len.lit else { return None; };
+			(Scalar::parse(&array.elem)?, len.base10_parse().ok()?)
+		},
+		Type::Tuple(tuple) =>
+		{
+			let scalar = Scalar::parse(tuple.elems.first()?)?;
+			if tuple.elems.iter().any(|elem| Scalar::parse(elem) != Some(scalar)) { return None; }
+			(scalar, tuple.elems.len() as u8)
+		},
+		Type::Paren(paren) => return infer(&paren.elem),
+		_ => return None
+	};
+	if (1..=4).contains(&size) { Some((scalar, size)) } else { None }
+}
+
+fn repr_c_packed(input: &DeriveInput) -> syn::Result<bool>
+{
+	let (mut c, mut packed) = (false, false);
+	for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr"))
+	{
+		attr.parse_nested_meta(|meta|
+		{
+			if meta.path.is_ident("C") { c = true; }
+			if meta.path.is_ident("packed") { packed = true; }
+			Ok(())
+		})?;
+	}
+	Ok(c && packed)
+}
+
+fn attributes_repr_cpacked(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+	let name = &input.ident;
+	if !input.generics.params.is_empty() { return Err(Error::new(input.generics.span(), "AttributesReprCpacked can not be derived for generic structs")); }
+	if !repr_c_packed(input)? { return Err(Error::new(name.span(), "AttributesReprCpacked needs #[repr(C, packed)]")); }
+	let Data::Struct(data) = &input.data else { return Err(Error::new(name.span(), "AttributesReprCpacked can only be derived for structs")); };
+	let Fields::Named(fields) = &data.fields else { return Err(Error::new(name.span(), "AttributesReprCpacked needs named fields")); };
+
+	let mut attributes = Vec::new();
+	let mut web_errors = Vec::new();
+	let mut bytes = 0;
+	let mut component = 1;
+	for field in &fields.named
+	{
+		let mut attribute = LitStr::new(&field.ident.as_ref().unwrap().to_string(), field.span());
+		let mut normalized = false;
+		for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("attribute"))
+		{
+			attr.parse_nested_meta(|meta|
+			{
+				if meta.path.is_ident("name") { attribute = meta.value()?.parse()?; }
+				else if meta.path.is_ident("normalized") { normalized = true; }
+				else { return Err(meta.error("expected `name = \"...\"` or `normalized`")); }
+				Ok(())
+			})?;
+		}
+		let Some((scalar, size)) = infer(&field.ty) else { return Err(Error::new(field.ty.span(), "unsupported attribute type (expected f32, i8, u8, i16, u16, i32 or u32 as scalar, [T; N], tuple or Vec2/Vec3/Vec4 with up to 4 components)")); };
+		if normalized && matches!(scalar, Scalar::F32 | Scalar::I32 | Scalar::U32) { return Err(Error::new(field.ty.span(), "only 8 and 16 bit integer attributes can be normalized")); }
+		//WebGL 1 needs every component aligned to its size, see Gl::new_vertex_buffer
+		if bytes % scalar.bytes() != 0 { return Err(Error::new(field.ty.span(), format!("the attribute {} is misaligned (its offset {} is not a multiple of {} bytes)", attribute.value(), bytes, scalar.bytes()))); }
+		component = component.max(scalar.bytes());
+		//BufferType::Int does not exist on wasm32, the target is only known when the generated code is compiled
+		if matches!(scalar, Scalar::I32 | Scalar::U32)
+		{
+			let message = format!("the attribute {} of {} is a 32 bit integer, which WebGL 1 does not support", attribute.value(), name);
+			web_errors.push(quote_spanned!(field.ty.span() => ::core::compile_error!(#message);));
+		}
+		let buffer_type = scalar.buffer_type(size, normalized);
+		attributes.push(quote!((#buffer_type, #attribute)));
+		bytes += scalar.bytes() * size as usize;
+	}
+
+	let message = format!("{} does not have the size of its attributes ({} bytes)", name, bytes);
+	let stride_message = format!("the size of {} has to be a multiple of {} bytes (its largest component)", name, component);
+	let cfg = if web_errors.is_empty() { quote!() } else { quote!(#[cfg(not(target_arch = "wasm32"))]) };
+	Ok(quote!
+	{
+		#cfg
+		impl ::gru_opengl::gl::AttributesReprCpacked for #name
+		{
+			const ATTRIBUTES: &'static [(::gru_opengl::gl::BufferType, &'static str)] = &[#(#attributes),*];
+		}
+
+		const _: () = assert!(::std::mem::size_of::<#name>() == #bytes, #message);
+		const _: () = assert!(::std::mem::size_of::<#name>() % #component == 0, #stride_message);
+
+		#[cfg(target_arch = "wasm32")]
+		const _: () = { #(#web_errors)* };
+	})
+}
+
+fn uniform_struct(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+	let name = &input.ident;
+	let Data::Struct(data) = &input.data else { return Err(Error::new(name.span(), "UniformStruct can only be derived for structs")); };
+	let Fields::Named(fields) = &data.fields else { return Err(Error::new(name.span(), "UniformStruct needs named fields")); };
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	let mut setters = Vec::new();
+	for field in &fields.named
+	{
+		let ident = field.ident.as_ref().unwrap();
+		let mut uniform = LitStr::new(&ident.to_string(), field.span());
+		let mut nested = false;
+		for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("uniform"))
+		{
+			attr.parse_nested_meta(|meta|
+			{
+				if meta.path.is_ident("name") { uniform = meta.value()?.parse()?; }
+				else if meta.path.is_ident("nested") { nested = true; }
+				else { return Err(meta.error("expected `name = \"...\"` or `nested`")); }
+				Ok(())
+			})?;
+		}
+		setters.push(if nested
+		{
+			quote!(pipeline.uniform_struct(&format!("{}.{}", name, #uniform), &self.#ident);)
+		}
+		else if let Type::Array(_) = field.ty
+		{
+			quote!(pipeline.uniform_name(&format!("{}.{}", name, #uniform), &self.#ident[..]);)
+		}
+		else
+		{
+			quote!(pipeline.uniform_name(&format!("{}.{}", name, #uniform), &self.#ident);)
+		});
+	}
+
+	Ok(quote!
+	{
+		impl #impl_generics ::gru_opengl::gl::UniformStruct for #name #ty_generics #where_clause
+		{
+			fn set_uniforms<T: ::gru_opengl::gl::VertexStreams, I: ::gru_opengl::gl::AttributesReprCpacked>(&self, pipeline: &mut ::gru_opengl::gl::Pipeline<T, I>, name: &str)
+			{
+				#(#setters)*
+			}
+		}
+	})
+}
@@ -7,40 +7,86 @@ impl Gl
 		let gl = &self.raw;
 		let buffer = unsafe
 		{
-			let buffer = gl.create_buffer().unwrap();
+			let buffer = gl.context().create_buffer().unwrap();
 			gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
-			gl.buffer_data_size(glow::ARRAY_BUFFER, length as i32 * std::mem::size_of::<T>() as i32, access.draw());
-			gl.bind_buffer(glow::ARRAY_BUFFER, None);
+			gl.context().buffer_data_size(glow::ARRAY_BUFFER, length as i32 * std::mem::size_of::<T>() as i32, access.draw());
 			buffer
 		};
-		let size_of_t: usize = T::ATTRIBUTES.iter().map(|(ty, _)| match ty
-		{
-			BufferType::Float { size } => *size as usize * 4,
-			#[cfg(not(target_arch = "wasm32"))]
-			BufferType::Int { size, .. } => *size as usize * 4
-		}).sum();
+		let size_of_t: usize = T::ATTRIBUTES.iter().map(|(ty, _)| ty.bytes() as usize).sum();
 		if size_of_t != std::mem::size_of::<T>() { panic!("Gl::new_vertex_buffer: Wrong attribute trait implementation (the struct size does not match)."); }
-		VertexBuffer { gl: gl.clone(), buffer, length, _phantom: PhantomData }
+		//WebGL 1 rejects the draw otherwise, so fail early on every platform
+		let mut offset = 0u32;
+		for (ty, name) in T::ATTRIBUTES
+		{
+			let component = ty.component_bytes();
+			if !offset.is_multiple_of(component) || !(size_of_t as u32).is_multiple_of(component) { panic!("Gl::new_vertex_buffer: The attribute \"{}\" is misaligned (its offset and the struct size have to be multiples of {} bytes).", name, component); }
+			offset += ty.bytes();
+		}
+		let id = self.buffer_id;
+		self.buffer_id += 1;
+		let raw = RawVertexBuffer { gl: gl.clone(), id, buffer, length, attributes: T::ATTRIBUTES, stride: size_of_t as u32 };
+		VertexBuffer { raw, _phantom: PhantomData }
+	}
+
+	#[inline]
+	pub fn new_index_buffer<I: IndexType>(&mut self, length: u32, access: BufferAccess) -> IndexBuffer<I>
+	{
+		self.try_new_index_buffer(length, access).unwrap_or_else(|err| panic!("Gl::new_index_buffer: {}", err))
 	}
 
-	pub fn new_index_buffer(&mut self, length: u32, access: BufferAccess) -> IndexBuffer
+	pub fn try_new_index_buffer<I: IndexType>(&mut self, length: u32, access: BufferAccess) -> Result<IndexBuffer<I>, String>
 	{
+		if I::CODE == glow::UNSIGNED_INT && !self.capabilities.uint_indices { return Err("32 bit indices are not supported (OES_element_index_uint is missing).".to_string()); }
 		let gl = &self.raw;
+		let size = std::mem::size_of::<I>() as u32;
+		let id = self.buffer_id;
+		self.buffer_id += 1;
 		unsafe
 		{
-			let buffer = gl.create_buffer().unwrap();
+			let buffer = gl.context().create_buffer().unwrap();
+			gl.bind_vertex_array(None);
 			gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(buffer));
-			gl.buffer_data_size(glow::ELEMENT_ARRAY_BUFFER, length as i32 * 2, access.draw());
-			gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
-			IndexBuffer { gl: gl.clone(), buffer, length }
+			gl.context().buffer_data_size(glow::ELEMENT_ARRAY_BUFFER, (length * size) as i32, access.draw());
+			Ok(IndexBuffer { raw: RawIndexBuffer { gl: gl.clone(), id, buffer, length, code: I::CODE, size }, _phantom: PhantomData })
 		}
 	}
+
+	#[inline]
+	pub fn new_stream_buffer<T: AttributesReprCpacked>(&mut self, capacity: u32) -> StreamBuffer<T>
+	{
+		StreamBuffer { buffer: self.new_vertex_buffer(capacity, BufferAccess::Stream), cursor: 0 }
+	}
+
+	#[inline]
+	pub fn new_index_stream_buffer<I: IndexType>(&mut self, capacity: u32) -> IndexStreamBuffer<I>
+	{
+		IndexStreamBuffer { buffer: self.new_index_buffer(capacity, BufferAccess::Stream), cursor: 0 }
+	}
 }
 
-#[derive(Clone, Copy)]
+//reserves length elements at the cursor, orphans the storage when full and grows geometrically if needed
+//the range ends at or before limit, later ranges restart at 0 once it is reached
+fn stream_range(gl: &Raw, target: u32, buffer_length: &mut u32, cursor: &mut u32, length: u32, size: u32, limit: u32) -> u32
+{
+	if *cursor as u64 + length as u64 > (*buffer_length).min(limit) as u64
+	{
+		if length > *buffer_length { *buffer_length = (*buffer_length * 2).max(length); }
+		//the old storage stays alive for pending draws
+		unsafe { gl.context().buffer_data_size(target, (*buffer_length * size) as i32, glow::STREAM_DRAW); }
+		*cursor = 0;
+	}
+	let offset = *cursor;
+	*cursor += length;
+	offset
+}
+
+//Byte and Short are passed to float attributes (normalized to [0, 1] or [-1, 1] if requested)
+#[derive(Clone, Copy, PartialEq)]
 pub enum BufferType
 {
 	Float { size: u8 },
+	Byte { signed: bool, normalized: bool, size: u8 },
+	Short { signed: bool, normalized: bool, size: u8 },
 	#[cfg(not(target_arch = "wasm32"))]
 	Int { signed: bool, size: u8 }
 }
@@ -49,39 +95,163 @@ impl BufferType
 {
 	pub(crate) fn code(&self) -> u32
 	{
-		#[cfg(not(target_arch = "wasm32"))]
 		match self
 		{
-			Self::Float { size: 1 } => glow::FLOAT,
-			Self::Float { size: 2 } => glow::FLOAT_VEC2,
-			Self::Float { size: 3 } => glow::FLOAT_VEC3,
-			Self::Float { size: 4 } => glow::FLOAT_VEC4,
+			Self::Float { size: 1 } | Self::Byte { size: 1, .. } | Self::Short { size: 1, .. } => glow::FLOAT,
+			Self::Float { size: 2 } | Self::Byte { size: 2, .. } | Self::Short { size: 2, .. } => glow::FLOAT_VEC2,
+			Self::Float { size: 3 } | Self::Byte { size: 3, .. } | Self::Short { size: 3, .. } => glow::FLOAT_VEC3,
+			Self::Float { size: 4 } | Self::Byte { size: 4, .. } | Self::Short { size: 4, .. } => glow::FLOAT_VEC4,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: true, size: 1 } => glow::INT,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: true, size: 2 } => glow::INT_VEC2,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: true, size: 3 } => glow::INT_VEC3,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: true, size: 4 } => glow::INT_VEC4,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: false, size: 1 } => glow::UNSIGNED_INT,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: false, size: 2 } => glow::UNSIGNED_INT_VEC2,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: false, size: 3 } => glow::UNSIGNED_INT_VEC3,
+			#[cfg(not(target_arch = "wasm32"))]
 			Self::Int { signed: false, size: 4 } => glow::UNSIGNED_INT_VEC4,
 			_ => panic!("Invalid BufferType.")
 		}
-		#[cfg(target_arch = "wasm32")]
+	}
+
+	pub(crate) fn data_type(&self) -> u32
+	{
 		match self
 		{
-			Self::Float { size: 1 } => glow::FLOAT,
-			Self::Float { size: 2 } => glow::FLOAT_VEC2,
-			Self::Float { size: 3 } => glow::FLOAT_VEC3,
-			Self::Float { size: 4 } => glow::FLOAT_VEC4,
-			_ => panic!("Invalid BufferType.")
+			Self::Float { .. } => glow::FLOAT,
+			Self::Byte { signed: true, .. } => glow::BYTE,
+			Self::Byte { signed: false, .. } => glow::UNSIGNED_BYTE,
+			Self::Short { signed: true, .. } => glow::SHORT,
+			Self::Short { signed: false, .. } => glow::UNSIGNED_SHORT,
+			#[cfg(not(target_arch = "wasm32"))]
+			Self::Int { signed: true, .. } => glow::INT,
+			#[cfg(not(target_arch = "wasm32"))]
+			Self::Int { signed: false, .. } => glow::UNSIGNED_INT
 		}
 	}
+
+	pub(crate) fn size(&self) -> u8
+	{
+		match self
+		{
+			Self::Float { size } | Self::Byte { size, .. } | Self::Short { size, .. } => *size,
+			#[cfg(not(target_arch = "wasm32"))]
+			Self::Int { size, .. } => *size
+		}
+	}
+
+	pub(crate) fn normalized(&self) -> bool
+	{
+		match self
+		{
+			Self::Byte { normalized, .. } | Self::Short { normalized, .. } => *normalized,
+			_ => false
+		}
+	}
+
+	pub(crate) fn component_bytes(&self) -> u32
+	{
+		match self
+		{
+			Self::Byte { .. } => 1,
+			Self::Short { .. } => 2,
+			_ => 4
+		}
+	}
+
+	//bytes in the vertex struct
+	pub(crate) fn bytes(&self) -> u32
+	{
+		self.size() as u32 * self.component_bytes()
+	}
 }
 
 pub trait AttributesReprCpacked
 {
     const ATTRIBUTES: &'static [(BufferType, &'static str)];
 }
+pub use gru_opengl_derive::AttributesReprCpacked;
+
+//no attributes, e.g. for shaders without per-instance data
+impl AttributesReprCpacked for ()
+{
+    const ATTRIBUTES: &'static [(BufferType, &'static str)] = &[];
+}
+
+//the vertex attributes of a shader split over one buffer per stream, e.g. Shader<(Position, Skinning)>, see Pipeline::draw_streams
+pub trait VertexStreams
+{
+	const STREAMS: &'static [&'static [(BufferType, &'static str)]];
+}
+
+impl<T: AttributesReprCpacked> VertexStreams for T
+{
+	const STREAMS: &'static [&'static [(BufferType, &'static str)]] = &[T::ATTRIBUTES];
+}
+
+macro_rules! vertex_streams
+{
+	($($stream: ident),+) =>
+	{
+		impl<$($stream: AttributesReprCpacked),+> VertexStreams for ($($stream,)+)
+		{
+			const STREAMS: &'static [&'static [(BufferType, &'static str)]] = &[$($stream::ATTRIBUTES),+];
+		}
+	}
+}
+
+vertex_streams!(A, B);
+vertex_streams!(A, B, C);
+vertex_streams!(A, B, C, D);
+
+/// # Safety
+/// CODE has to be the GL index type with size_of::<Self>() bytes, the data is uploaded as raw bytes.
+/// MAX is the largest index that from_u32 accepts.
+pub unsafe trait IndexType: Copy
+{
+	const CODE: u32;
+	const MAX: u32;
+	fn from_u32(index: u32) -> Option<Self>;
+	fn to_u32(self) -> u32;
+}
+
+unsafe impl IndexType for u8
+{
+	const CODE: u32 = glow::UNSIGNED_BYTE;
+	const MAX: u32 = u8::MAX as u32;
+	#[inline]
+	fn from_u32(index: u32) -> Option<Self> { index.try_into().ok() }
+	#[inline]
+	fn to_u32(self) -> u32 { self as u32 }
+}
+
+unsafe impl IndexType for u16
+{
+	const CODE: u32 = glow::UNSIGNED_SHORT;
+	const MAX: u32 = u16::MAX as u32;
+	#[inline]
+	fn from_u32(index: u32) -> Option<Self> { index.try_into().ok() }
+	#[inline]
+	fn to_u32(self) -> u32 { self as u32 }
+}
+
+//needs OES_element_index_uint on WebGL 1
+unsafe impl IndexType for u32
+{
+	const CODE: u32 = glow::UNSIGNED_INT;
+	const MAX: u32 = u32::MAX;
+	#[inline]
+	fn from_u32(index: u32) -> Option<Self> { Some(index) }
+	#[inline]
+	fn to_u32(self) -> u32 { self }
+}
 
 #[derive(Clone, Copy)]
 pub enum BufferAccess
@@ -104,50 +274,161 @@ impl BufferAccess
 	}
 }
 
-impl<T: AttributesReprCpacked> VertexBuffer<T>
+impl RawVertexBuffer
 {
 	#[inline]
 	pub fn len(&self) -> u32
 	{
 		self.length
 	}
+}
 
+impl<T: AttributesReprCpacked> VertexBuffer<T>
+{
 	#[inline]
 	pub fn data(&mut self, offset: u32, data: &[T])
 	{
-		if offset + data.len() as u32 > self.length { panic!("VertexBuffer::data: Too much data."); }
-		let gl = &self.gl;
+		if offset + data.len() as u32 > self.raw.length { panic!("VertexBuffer::data: Too much data."); }
+		let gl = &self.raw.gl;
 		unsafe
 		{
 			let ptr = data.as_ptr() as *const u8;
 			let data = std::slice::from_raw_parts(ptr, data.len() * std::mem::size_of::<T>());
-			gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
-			gl.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, offset as i32 * std::mem::size_of::<T>() as i32, data);
-			gl.bind_buffer(glow::ARRAY_BUFFER, None);
+			gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.raw.buffer));
+			gl.context().buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, offset as i32 * std::mem::size_of::<T>() as i32, data);
 		}
 	}
 }
 
-impl IndexBuffer
+impl<T: AttributesReprCpacked> std::ops::Deref for VertexBuffer<T>
+{
+	type Target = RawVertexBuffer;
+
+	#[inline]
+	fn deref(&self) -> &RawVertexBuffer
+	{
+		&self.raw
+	}
+}
+
+impl RawIndexBuffer
 {
 	#[inline]
 	pub fn len(&self) -> u32
 	{
 		self.length
 	}
-	
+}
+
+impl<I: IndexType> IndexBuffer<I>
+{
 	#[inline]
-	pub fn data(&mut self, offset: u32, data: &[u16])
+	pub fn data(&mut self, offset: u32, data: &[I])
 	{
-		if offset + data.len() as u32 > self.length { panic!("IndexBuffer::data: Too much data."); }
-		let gl = &self.gl;
+		if offset + data.len() as u32 > self.raw.length { panic!("IndexBuffer::data: Too much data."); }
+		let gl = &self.raw.gl;
 		unsafe
 		{
 			let ptr = data.as_ptr() as *const u8;
-			let data = std::slice::from_raw_parts(ptr, data.len() * 2);
-			gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.buffer));
-			gl.buffer_sub_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, offset as i32 * 2, data);
-			gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
+			let data = std::slice::from_raw_parts(ptr, data.len() * std::mem::size_of::<I>());
+			gl.bind_vertex_array(None);
+			gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.raw.buffer));
+			gl.context().buffer_sub_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, (offset * self.raw.size) as i32, data);
+		}
+	}
+}
+
+impl<I: IndexType> std::ops::Deref for IndexBuffer<I>
+{
+	type Target = RawIndexBuffer;
+
+	#[inline]
+	fn deref(&self) -> &RawIndexBuffer
+	{
+		&self.raw
+	}
+}
+
+//a pushed range stays valid until a later push wraps around, so draw it before pushing again
+impl<T: AttributesReprCpacked> StreamBuffer<T>
+{
+	//returns the offset of the first vertex for Pipeline::draw
+	#[inline]
+	pub fn push(&mut self, data: &[T]) -> u32
+	{
+		self.push_range(data, u32::MAX)
+	}
+
+	//like push, but every pushed vertex stays addressable by I when the offset is used as base_vertex of IndexStreamBuffer::push
+	#[inline]
+	pub fn push_indexed<I: IndexType>(&mut self, data: &[T]) -> u32
+	{
+		if data.len() as u64 > I::MAX as u64 + 1 { panic!("StreamBuffer::push_indexed: Too many vertices for the index type."); }
+		self.push_range(data, I::MAX.saturating_add(1))
+	}
+
+	fn push_range(&mut self, data: &[T], limit: u32) -> u32
+	{
+		let raw = &mut self.buffer.raw;
+		let gl = &raw.gl;
+		let size = std::mem::size_of::<T>() as u32;
+		unsafe { gl.bind_buffer(glow::ARRAY_BUFFER, Some(raw.buffer)); }
+		let offset = stream_range(gl, glow::ARRAY_BUFFER, &mut raw.length, &mut self.cursor, data.len() as u32, size, limit);
+		unsafe
+		{
+			let data = std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * size as usize);
+			gl.context().buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, (offset * size) as i32, data);
 		}
+		offset
+	}
+}
+
+impl<T: AttributesReprCpacked> std::ops::Deref for StreamBuffer<T>
+{
+	type Target = VertexBuffer<T>;
+
+	#[inline]
+	fn deref(&self) -> &VertexBuffer<T>
+	{
+		&self.buffer
+	}
+}
+
+impl<I: IndexType> IndexStreamBuffer<I>
+{
+	//base_vertex (see StreamBuffer::push_indexed) is added to every index, returns the offset of the first index for Pipeline::draw
+	pub fn push(&mut self, data: &[I], base_vertex: u32) -> u32
+	{
+		let raw = &mut self.buffer.raw;
+		let gl = &raw.gl;
+		unsafe
+		{
+			gl.bind_vertex_array(None);
+			gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(raw.buffer));
+		}
+		let offset = stream_range(gl, glow::ELEMENT_ARRAY_BUFFER, &mut raw.length, &mut self.cursor, data.len() as u32, raw.size, u32::MAX);
+		let rebased: Vec<I>;
+		let data = if base_vertex == 0 { data } else
+		{
+			rebased = data.iter().map(|index| index.to_u32().checked_add(base_vertex).and_then(I::from_u32).unwrap_or_else(|| panic!("IndexStreamBuffer::push: The rebased index does not fit the index type (push the vertices with StreamBuffer::push_indexed)."))).collect();
+			&rebased
+		};
+		unsafe
+		{
+			let data = std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * raw.size as usize);
+			gl.context().buffer_sub_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, (offset * raw.size) as i32, data);
+		}
+		offset
+	}
+}
+
+impl<I: IndexType> std::ops::Deref for IndexStreamBuffer<I>
+{
+	type Target = IndexBuffer<I>;
+
+	#[inline]
+	fn deref(&self) -> &IndexBuffer<I>
+	{
+		&self.buffer
 	}
 }
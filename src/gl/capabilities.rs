@@ -0,0 +1,117 @@
+use super::*;
+
+const ALIASED_POINT_SIZE_RANGE: u32 = 0x846D; //missing in glow
+
+//context dependent limits and features, gathered once in Gl::new
+#[derive(Clone, Debug)]
+pub struct Capabilities
+{
+	pub renderer: String,
+	pub vendor: String,
+	pub version: String,
+	pub extensions: Vec<String>, //sorted, with the "GL_" prefix on desktop
+	pub max_texture_size: u32,
+	pub max_cube_texture_size: u32,
+	pub max_texture_units: u32, //MAX_TEXTURE_IMAGE_UNITS, shaders can use one less
+	pub max_vertex_attributes: u32,
+	pub line_width_range: (f32, f32), //most drivers only support 1
+	pub point_size_range: (f32, f32), //for gl_PointSize
+	pub max_draw_buffers: u32, //color attachments of a Framebuffer, 1 on WebGL 1 without WEBGL_draw_buffers
+	//features used by the crate
+	pub depth_texture: bool, //FramebufferDepth::Texture
+	pub instancing: bool, //Pipeline::draw_instanced
+	pub uint_indices: bool, //IndexBuffer<u32>
+	pub vertex_array_object: bool, //used internally by Pipeline::draw
+	pub derivatives: bool, //dFdx, dFdy and fwidth in fragment shaders
+	pub float_textures: bool, //TextureChannel::RGBFloat and RGBAFloat
+	pub half_float_textures: bool, //TextureChannel::RGBHalf and RGBAHalf
+	pub float_render_targets: bool, //float Framebuffer color attachments
+	pub half_float_render_targets: bool, //half float Framebuffer color attachments
+	pub max_anisotropy: Option<f32> //TextureFilter::anisotropy, None without EXT_texture_filter_anisotropic
+}
+
+impl Capabilities
+{
+	pub(crate) fn new(gl: &Context) -> Self
+	{
+		let extension = |name: &str| gl.supported_extensions().contains(name);
+		let version = |major: u32, minor: u32| (gl.version().major, gl.version().minor) >= (major, minor);
+		let parameter = |parameter: u32| unsafe { gl.get_parameter_i32(parameter) } as u32;
+		let range = |parameter: u32|
+		{
+			let mut range = [1.0; 2];
+			unsafe { gl.get_parameter_f32_slice(parameter, &mut range); }
+			//broken drivers report inverted or NaN ranges, which f32::clamp rejects
+			if range[0] <= range[1] { (range[0], range[1]) } else { (1.0, 1.0) }
+		};
+		let web = cfg!(target_arch = "wasm32");
+		let mut extensions: Vec<String> = gl.supported_extensions().iter().cloned().collect();
+		extensions.sort();
+		Self
+		{
+			renderer: unsafe { gl.get_parameter_string(glow::RENDERER) },
+			vendor: unsafe { gl.get_parameter_string(glow::VENDOR) },
+			version: unsafe { gl.get_parameter_string(glow::VERSION) },
+			extensions,
+			max_texture_size: parameter(glow::MAX_TEXTURE_SIZE),
+			max_cube_texture_size: parameter(glow::MAX_CUBE_MAP_TEXTURE_SIZE),
+			max_texture_units: parameter(glow::MAX_TEXTURE_IMAGE_UNITS).max(1), //the scratch unit of Raw::edit_texture has to exist
+			max_vertex_attributes: parameter(glow::MAX_VERTEX_ATTRIBS),
+			line_width_range: range(glow::ALIASED_LINE_WIDTH_RANGE),
+			point_size_range: range(ALIASED_POINT_SIZE_RANGE),
+			max_draw_buffers: if web && !extension("WEBGL_draw_buffers") { 1 } else { parameter(glow::MAX_DRAW_BUFFERS).min(parameter(glow::MAX_COLOR_ATTACHMENTS)).max(1) },
+			depth_texture: if web { extension("WEBGL_depth_texture") } else { true }, //core since OpenGL 1.4
+			instancing: if web { extension("ANGLE_instanced_arrays") } else { version(3, 3) || extension("GL_ARB_instanced_arrays") },
+			uint_indices: if web { extension("OES_element_index_uint") } else { true },
+			vertex_array_object: if web { extension("OES_vertex_array_object") } else { version(3, 0) || extension("GL_ARB_vertex_array_object") },
+			derivatives: if web { extension("OES_standard_derivatives") } else { true },
+			float_textures: if web { extension("OES_texture_float") } else { version(3, 0) || extension("GL_ARB_texture_float") },
+			half_float_textures: if web { extension("OES_texture_half_float") } else { version(3, 0) || (extension("GL_ARB_texture_float") && extension("GL_ARB_half_float_pixel")) },
+			float_render_targets: if web { extension("WEBGL_color_buffer_float") || extension("EXT_color_buffer_float") } else { version(3, 0) || extension("GL_ARB_color_buffer_float") },
+			half_float_render_targets: if web { extension("EXT_color_buffer_half_float") } else { version(3, 0) || extension("GL_ARB_color_buffer_float") },
+			max_anisotropy: ["EXT_texture_filter_anisotropic", "GL_EXT_texture_filter_anisotropic", "GL_ARB_texture_filter_anisotropic"].iter()
+				.any(|name| extension(name))
+				.then(|| unsafe { gl.get_parameter_f32(glow::MAX_TEXTURE_MAX_ANISOTROPY_EXT) })
+				.filter(|max| *max >= 1.0) //TextureFilter::apply clamps to [1, max]
+		}
+	}
+}
+
+impl Capabilities
+{
+	//why textures with the channel can not be created, see Gl::new_texture
+	pub fn check_texture(&self, channel: TextureChannel) -> Result<(), String>
+	{
+		let web = cfg!(target_arch = "wasm32");
+		if channel.float() && !self.float_textures { return Err(format!("Float textures are not supported ({} is missing).", if web { "OES_texture_float" } else { "OpenGL 3.0 or GL_ARB_texture_float" })); }
+		if channel.half() && !self.half_float_textures { return Err(format!("Half float textures are not supported ({} is missing).", if web { "OES_texture_half_float" } else { "OpenGL 3.0 or GL_ARB_half_float_pixel" })); }
+		Ok(())
+	}
+
+	//why the channel can not be used for Framebuffer color attachments, see Gl::new_framebuffer
+	pub fn check_render_target(&self, channel: TextureChannel) -> Result<(), String>
+	{
+		let web = cfg!(target_arch = "wasm32");
+		match channel
+		{
+			TextureChannel::A => return Err("Alpha textures can not be rendered to.".to_string()),
+			TextureChannel::Depth | TextureChannel::DepthStencil => return Err("Depth attachments are configured with FramebufferConfig::depth.".to_string()),
+			//WEBGL_color_buffer_float and EXT_color_buffer_half_float only guarantee RGBA
+			TextureChannel::RGBFloat | TextureChannel::RGBHalf if web => return Err("Only RGBA float textures can be rendered to on WebGL 1.".to_string()),
+			_ => {}
+		}
+		self.check_texture(channel)?;
+		if channel.float() && !self.float_render_targets { return Err(format!("Float textures can not be rendered to ({} is missing).", if web { "WEBGL_color_buffer_float" } else { "OpenGL 3.0 or GL_ARB_color_buffer_float" })); }
+		if channel.half() && !self.half_float_render_targets { return Err(format!("Half float textures can not be rendered to ({} is missing).", if web { "EXT_color_buffer_half_float" } else { "OpenGL 3.0 or GL_ARB_color_buffer_float" })); }
+		Ok(())
+	}
+}
+
+impl Gl
+{
+	#[inline]
+	pub fn capabilities(&self) -> &Capabilities
+	{
+		&self.capabilities
+	}
+}
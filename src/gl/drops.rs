@@ -1,22 +1,38 @@
 use super::*;
 
-impl<T: AttributesReprCpacked> Drop for VertexBuffer<T>
+impl Drop for RawVertexBuffer
 {
 	fn drop(&mut self)
 	{
-		unsafe { self.gl.delete_buffer(self.buffer); }
+		unsafe
+		{
+			self.gl.delete_vertex_arrays(|key| key.uses_buffer(self.id));
+			self.gl.delete_buffer(self.buffer);
+		}
+	}
+}
+
+impl Drop for RawIndexBuffer
+{
+	fn drop(&mut self)
+	{
+		unsafe
+		{
+			self.gl.delete_vertex_arrays(|key| key.uses_buffer(self.id));
+			self.gl.delete_buffer(self.buffer);
+		}
 	}
 }
 
-impl Drop for IndexBuffer
+impl Drop for Texture
 {
 	fn drop(&mut self)
 	{
-		unsafe { self.gl.delete_buffer(self.buffer); }
+		unsafe { self.gl.delete_texture(self.texture); }
 	}
 }
 
-impl<const P: bool> Drop for Texture<P>
+impl Drop for CubeTexture
 {
 	fn drop(&mut self)
 	{
@@ -24,11 +40,15 @@ impl<const P: bool> Drop for Texture<P>
 	}
 }
 
-impl<T: AttributesReprCpacked> Drop for Shader<T>
+impl<T: VertexStreams, I: AttributesReprCpacked> Drop for Shader<T, I>
 {
 	fn drop(&mut self)
 	{
-		unsafe { self.gl.delete_program(self.program) };
+		unsafe
+		{
+			self.gl.delete_vertex_arrays(|key| key.shader == self.id);
+			self.gl.delete_program(self.program);
+		}
 	}
 }
 
@@ -38,7 +58,7 @@ impl Drop for Framebuffer
 	{
 		unsafe
 		{
-			if let Some(renderbuffer) = self.depth { self.gl.delete_renderbuffer(renderbuffer); }
+			if let Some(renderbuffer) = self.renderbuffer { self.gl.context().delete_renderbuffer(renderbuffer); }
 			self.gl.delete_framebuffer(self.framebuffer);
 		}
 	}
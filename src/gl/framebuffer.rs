@@ -2,61 +2,141 @@ use super::*;
 
 impl Gl
 {
-	pub fn new_framebuffer(&mut self, FramebufferConfig { depth, size, wrap }: &FramebufferConfig) -> Framebuffer
+	pub fn new_framebuffer(&mut self, FramebufferConfig { width, height, colors, depth, stencil, filter, wrap }: &FramebufferConfig) -> Framebuffer
 	{
-		if size & (size - 1) != 0 { panic!("Gl::new_framebuffer: Size is not a power of 2."); }
-		let gl = &self.raw;
-		unsafe
+		if colors.is_empty() { panic!("Gl::new_framebuffer: At least one color attachment is needed."); }
+		if colors.len() as u32 > self.capabilities.max_draw_buffers { panic!("Gl::new_framebuffer: Only {} color attachments are supported (WEBGL_draw_buffers is missing or the limit is exceeded).", self.capabilities.max_draw_buffers); }
+		if let Some(err) = colors.iter().find_map(|color| self.capabilities.check_render_target(*color).err()) { panic!("Gl::new_framebuffer: {}", err); }
+		if *depth == FramebufferDepth::Texture && !self.capabilities.depth_texture { panic!("Gl::new_framebuffer: Depth textures are not supported (WEBGL_depth_texture is missing)."); }
+		let framebuffer = unsafe { self.raw.context().create_framebuffer() }.unwrap();
+		unsafe { self.raw.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer)); }
+
+		let colors: Vec<_> = colors.iter().enumerate().map(|(i, color)|
+		{
+			let config = TextureConfig { width: *width, height: *height, channel: *color, mipmap: false, filter: *filter, wrap_s: *wrap, wrap_t: *wrap };
+			let texture = self.raw_texture(&config, None);
+			unsafe { self.raw.context().framebuffer_texture_2d(glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0 + i as u32, glow::TEXTURE_2D, Some(texture.texture), 0); }
+			texture
+		}).collect();
+		//the draw buffers are framebuffer state, so every RenderTarget::Texture pass writes to all attachments
+		if colors.len() > 1
 		{
-			let framebuffer = gl.create_framebuffer().unwrap();
-			gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
-
-			let color = gl.create_texture().unwrap();
-			gl.bind_texture(glow::TEXTURE_2D, Some(color));
-			gl.tex_image_2d(glow::TEXTURE_2D, 0, glow::RGB as i32, *size as i32, *size as i32, 0, glow::RGB, glow::UNSIGNED_BYTE, None);
-			gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap.wrap() as i32);
-			gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap.wrap() as i32);
-			gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
-			gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
-			gl.bind_texture(glow::TEXTURE_2D, None);
-			gl.framebuffer_texture_2d(glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0, glow::TEXTURE_2D, Some(color), 0);
-			let color = Texture { gl: gl.clone(), texture: color, size: *size };
-
-			let depth = if *depth
-			{
-				let renderbuffer = gl.create_renderbuffer().unwrap();
-				gl.bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
-				gl.renderbuffer_storage(glow::RENDERBUFFER, glow::DEPTH_COMPONENT24, *size as i32, *size as i32);
-				gl.bind_renderbuffer(glow::RENDERBUFFER, None);
-				gl.framebuffer_renderbuffer(glow::FRAMEBUFFER, glow::DEPTH_ATTACHMENT, glow::RENDERBUFFER, Some(renderbuffer));
-				Some(renderbuffer)
-			} else { None };
-
-			gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-			Framebuffer { gl: gl.clone(), framebuffer, color, depth }
+			let attachments: Vec<_> = (0..colors.len() as u32).map(|i| glow::COLOR_ATTACHMENT0 + i).collect();
+			unsafe { self.raw.context().draw_buffers(&attachments); }
 		}
+
+		let depth_texture = if *depth == FramebufferDepth::Texture
+		{
+			let channel = if *stencil { TextureChannel::DepthStencil } else { TextureChannel::Depth };
+			let config = TextureConfig { width: *width, height: *height, channel, mipmap: false, filter: TextureFilter::NEAREST, wrap_s: TextureWrap::Clamp, wrap_t: TextureWrap::Clamp };
+			let texture = self.raw_texture(&config, None);
+			for attachment in Self::depth_attachments(*stencil) { unsafe { self.raw.context().framebuffer_texture_2d(glow::FRAMEBUFFER, *attachment, glow::TEXTURE_2D, Some(texture.texture), 0); } }
+			Some(texture)
+		} else { None };
+
+		let gl = &self.raw;
+		let renderbuffer = match (depth, stencil)
+		{
+			(FramebufferDepth::None, false) | (FramebufferDepth::Texture, _) => None,
+			(FramebufferDepth::None, true) => Some((glow::STENCIL_INDEX8, &[glow::STENCIL_ATTACHMENT][..])),
+			#[cfg(not(target_arch = "wasm32"))]
+			(FramebufferDepth::Renderbuffer, false) => Some((glow::DEPTH_COMPONENT24, &[glow::DEPTH_ATTACHMENT][..])),
+			#[cfg(target_arch = "wasm32")]
+			(FramebufferDepth::Renderbuffer, false) => Some((glow::DEPTH_COMPONENT16, &[glow::DEPTH_ATTACHMENT][..])),
+			#[cfg(not(target_arch = "wasm32"))]
+			(FramebufferDepth::Renderbuffer, true) => Some((glow::DEPTH24_STENCIL8, Self::depth_attachments(true))),
+			#[cfg(target_arch = "wasm32")]
+			(FramebufferDepth::Renderbuffer, true) => Some((glow::DEPTH_STENCIL, Self::depth_attachments(true)))
+		}.map(|(format, attachments)| unsafe
+		{
+			let renderbuffer = gl.context().create_renderbuffer().unwrap();
+			gl.context().bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+			gl.context().renderbuffer_storage(glow::RENDERBUFFER, format, *width as i32, *height as i32);
+			gl.context().bind_renderbuffer(glow::RENDERBUFFER, None);
+			for attachment in attachments { gl.context().framebuffer_renderbuffer(glow::FRAMEBUFFER, *attachment, glow::RENDERBUFFER, Some(renderbuffer)); }
+			renderbuffer
+		});
+
+		let status = unsafe { gl.context().check_framebuffer_status(glow::FRAMEBUFFER) };
+		if status != glow::FRAMEBUFFER_COMPLETE { panic!("Gl::new_framebuffer: The framebuffer is incomplete ({:#X}).", status); }
+		Framebuffer { gl: gl.clone(), framebuffer, colors, depth_texture, renderbuffer }
+	}
+
+	//WebGL 1 has a combined attachment point, OpenGL 2.0 needs both
+	const fn depth_attachments(stencil: bool) -> &'static [u32]
+	{
+		if !stencil { &[glow::DEPTH_ATTACHMENT] }
+		else if cfg!(target_arch = "wasm32") { &[glow::DEPTH_STENCIL_ATTACHMENT] }
+		else { &[glow::DEPTH_ATTACHMENT, glow::STENCIL_ATTACHMENT] }
 	}
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum FramebufferDepth
+{
+	None,
+	Renderbuffer, //cannot be sampled
+	Texture //WebGL 1 needs WEBGL_depth_texture
+}
+
 #[derive(Clone)]
 pub struct FramebufferConfig
 {
-	pub depth: bool,
-	pub size: u32,
+	pub width: u32,
+	pub height: u32,
+	pub colors: Vec<TextureChannel>, //one per color attachment (see Capabilities::check_render_target), gl_FragData[i] writes to attachment i, see Capabilities::max_draw_buffers
+	pub depth: FramebufferDepth,
+	pub stencil: bool,
+	pub filter: TextureFilter,
 	pub wrap: TextureWrap
 }
 
 impl Framebuffer
 {
 	#[inline]
-	pub fn size(&self) -> u32
+	pub fn width(&self) -> u32
+	{
+		self.colors[0].width
+	}
+
+	#[inline]
+	pub fn height(&self) -> u32
+	{
+		self.colors[0].height
+	}
+
+	#[inline]
+	pub fn size(&self) -> (u32, u32)
+	{
+		self.colors[0].size()
+	}
+
+	//the first color attachment
+	#[inline]
+	pub fn texture(&self) -> &Texture
+	{
+		&self.colors[0]
+	}
+
+	//one texture per color attachment in the order of FramebufferConfig::colors
+	#[inline]
+	pub fn textures(&self) -> &[Texture]
 	{
-		self.color.size
+		&self.colors
 	}
-	
+
+	//see Gl::read_pixels, reads the first color attachment
+	pub fn read_pixels(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<u8>
+	{
+		if x + width > self.width() || y + height > self.height() { panic!("Framebuffer::read_pixels: Region exceeds the framebuffer size."); }
+		if self.colors[0].channel.float() || self.colors[0].channel.half() { panic!("Framebuffer::read_pixels: Only 8 bit color attachments can be read."); }
+		Gl::read_pixels_raw(&self.gl, Some(self.framebuffer), x, y, width, height)
+	}
+
+	//only with FramebufferDepth::Texture
 	#[inline]
-	pub fn texture(&self) -> &Texture<true>
+	pub fn depth_texture(&self) -> Option<&Texture>
 	{
-		&self.color
+		self.depth_texture.as_ref()
 	}
 }
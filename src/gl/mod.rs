@@ -1,6 +1,6 @@
 use super::log;
 use glow::{Context, HasContext};
-use std::{rc::Rc, marker::PhantomData};
+use std::{rc::Rc, cell::RefCell, marker::PhantomData};
 use ahash::AHashMap;
 
 mod drops;
@@ -9,23 +9,33 @@ mod texture;
 mod shader;
 mod render;
 mod framebuffer;
+mod capabilities;
+mod state;
 pub use buffer::*;
 pub use texture::*;
 pub use shader::*;
 pub use render::*;
 pub use framebuffer::*;
+pub use capabilities::Capabilities;
+pub use state::StateStats;
+use state::Raw;
 
 pub struct Gl
 {
 	pub(crate) window_dims: (u32, u32),
-	raw: Rc<Context>,
+	raw: Rc<Raw>,
 	glsl_vertex_header: &'static str,
 	glsl_fragment_header: &'static str,
+	capabilities: Capabilities,
 	shader_id: u32,
-	viewport: (i32, i32),
+	buffer_id: u32,
+	viewport: (i32, i32, i32, i32), //(x, y, width, height)
+	scissor: Option<(i32, i32, i32, i32)>,
 	clear_color: (f32, f32, f32),
+	clear_stencil: i32,
 	attributes: AHashMap<String, u32>,
-	pipeline: PipelineInfo
+	pipeline: PipelineInfo,
+	blend_functions: (BlendFunction, BlendFunction) //kept while blending is disabled
 }
 
 impl Gl
@@ -41,30 +51,38 @@ impl Gl
 			gl.enable(glow::DEPTH_TEST);
 			gl.depth_func(glow::LEQUAL);
 			gl.disable(glow::BLEND);
-			gl.blend_equation(glow::FUNC_ADD);
-			gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+			BlendMode::Alpha.apply(&gl);
 			gl.enable(glow::CULL_FACE);
 			gl.cull_face(glow::BACK);
 
 			gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+
+			//gl_PointSize and gl_PointCoord are always available in WebGL
+			#[cfg(not(target_arch = "wasm32"))]
+			{
+				const POINT_SPRITE: u32 = 0x8861; //missing in glow, core in OpenGL 2.0 compatibility
+				gl.enable(glow::PROGRAM_POINT_SIZE);
+				gl.enable(POINT_SPRITE);
+			}
 		}
 
+		let capabilities = Capabilities::new(&gl);
 		Self
 		{
 			window_dims: (0, 0),
-			raw: Rc::new(gl),
+			raw: Rc::new(Raw::new(gl, &capabilities)),
+			capabilities,
 			glsl_vertex_header,
 			glsl_fragment_header,
 			shader_id: 0,
-			viewport: (-1, -1),
+			buffer_id: 0,
+			viewport: (-1, -1, -1, -1),
+			scissor: None,
 			clear_color: (0.0, 0.0, 0.0),
+			clear_stencil: 0,
 			attributes: AHashMap::new(),
-			pipeline: PipelineInfo
-			{
-				depth_test: true,
-				alpha_blend: false,
-				face_cull: true
-			}
+			pipeline: PipelineInfo::DEFAULT,
+			blend_functions: BlendMode::Alpha.functions()
 		}
 	}
 
@@ -86,48 +104,117 @@ impl Gl
 
 pub struct VertexBuffer<T: AttributesReprCpacked>
 {
-	gl: Rc<Context>,
+	raw: RawVertexBuffer,
+	_phantom: PhantomData<T>
+}
+//type erased VertexBuffer as used by Pipeline::draw_streams
+pub struct RawVertexBuffer
+{
+	gl: Rc<Raw>,
+	id: u32,
 	buffer: <Context as HasContext>::Buffer,
 	length: u32,
-	_phantom: PhantomData<T>
+	attributes: &'static [(BufferType, &'static str)],
+	stride: u32
 }
-//u16 indices
-pub struct IndexBuffer
+//ring buffers for per-frame geometry, see Gl::new_stream_buffer
+pub struct StreamBuffer<T: AttributesReprCpacked>
 {
-	gl: Rc<Context>,
+	buffer: VertexBuffer<T>,
+	cursor: u32
+}
+pub struct IndexStreamBuffer<I: IndexType = u16>
+{
+	buffer: IndexBuffer<I>,
+	cursor: u32
+}
+
+//identifies a vertex array object in Raw, which deletes it together with the shader or any of the buffers
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct VaoKey
+{
+	shader: u32,
+	streams: [Option<u32>; 4],
+	indices: Option<u32>,
+	instances: Option<u32>
+}
+
+impl VaoKey
+{
+	#[inline]
+	fn uses_buffer(&self, id: u32) -> bool
+	{
+		self.streams.contains(&Some(id)) || self.indices == Some(id) || self.instances == Some(id)
+	}
+}
+pub struct IndexBuffer<I: IndexType = u16>
+{
+	raw: RawIndexBuffer,
+	_phantom: PhantomData<I>
+}
+//type erased IndexBuffer as used by Pipeline::draw
+pub struct RawIndexBuffer
+{
+	gl: Rc<Raw>,
+	id: u32,
 	buffer: <Context as HasContext>::Buffer,
-	length: u32
+	length: u32,
+	code: u32, //glow type
+	size: u32 //bytes per index
 }
-//P: texture locks uniform location during the entire pipeline (8 in total!)
-pub struct Texture<const P: bool>
+pub struct Texture
 {
-	gl: Rc<Context>,
+	gl: Rc<Raw>,
 	texture: <Context as HasContext>::Texture,
-	size: u32
+	width: u32,
+	height: u32,
+	channel: TextureChannel,
+	mipmap: bool
 }
 
-pub struct Shader<T: AttributesReprCpacked>
+pub struct CubeTexture
 {
-	gl: Rc<Context>,
+	gl: Rc<Raw>,
+	texture: <Context as HasContext>::Texture,
+	size: u32,
+	channel: TextureChannel,
+	mipmap: bool
+}
+
+//I: per-instance attributes, see Pipeline::draw_instanced
+pub struct Shader<T: VertexStreams, I: AttributesReprCpacked = ()>
+{
+	gl: Rc<Raw>,
 	id: u32,
 	program: <Context as HasContext>::Program,
-	uniforms: AHashMap<String, (<Context as HasContext>::UniformLocation, u32)>, //(shader name, opengl location, glow type)
-	attributes: Vec<(BufferType, u32, i32)>, //(gru type, location, offset)
-	_phantom: PhantomData<T>
+	uniforms: AHashMap<String, (<Context as HasContext>::UniformLocation, u32, u32, u32)>, //(shader name, opengl location, glow type, array size, first texture unit of samplers)
+	streams: Vec<Vec<(BufferType, u32, i32)>>, //(gru type, location, offset) per vertex stream
+	instance_attributes: Vec<(BufferType, u32, i32)>, //(gru type, location, offset)
+	_phantom: PhantomData<(T, I)>
+}
+
+//all permutations of a shader source, see Gl::new_shader_variants
+pub struct ShaderVariants<T: VertexStreams, I: AttributesReprCpacked = ()>
+{
+	flags: Vec<String>,
+	shaders: Vec<Shader<T, I>> //indexed by the bitmask of enabled flags
 }
 
 pub struct Framebuffer
 {
-	gl: Rc<Context>,
+	gl: Rc<Raw>,
 	framebuffer: <Context as HasContext>::Framebuffer,
-	color: Texture<true>,
-	depth: Option<<Context as HasContext>::Renderbuffer>
+	colors: Vec<Texture>, //one per color attachment
+	depth_texture: Option<Texture>,
+	renderbuffer: Option<<Context as HasContext>::Renderbuffer> //depth and/or stencil
 }
 
-pub struct UniformKey<U: shader::UniformType>
+pub struct UniformKey<U: shader::UniformType + ?Sized>
 {
 	key: <Context as HasContext>::UniformLocation,
 	shader_id: u32,
+	size: u32,
+	unit: u32,
 	_phatom: PhantomData<U>
 }
 
@@ -140,14 +227,11 @@ pub enum RenderTarget<'a>
 pub struct RenderPass<'a, 'b>
 {
 	gl: &'a mut Gl,
-	render_target: RenderTarget<'b>
+	_render_target: PhantomData<RenderTarget<'b>> //the framebuffer stays borrowed, so its textures can not be sampled during the pass
 }
 
-pub struct Pipeline<'a, 'b, T: AttributesReprCpacked>
+pub struct Pipeline<'a, 'b, T: VertexStreams, I: AttributesReprCpacked = ()>
 {
 	gl: &'a mut Gl,
-	shader: &'b Shader<T>,
-	texture_active: u8,
-	texture_lock: u8,
-	texture_used: bool
+	shader: &'b Shader<T, I>
 }
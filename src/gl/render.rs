@@ -1,44 +1,390 @@
 use super::*;
 use crate::DEBUG;
 
+//all macros count issued and skipped state changes, see Gl::state_stats
 macro_rules! gl_able
 {
 	($gl: ident, $info: ident, $self: expr, $field: ident, $gl_name: ident) =>
 	{
-		if $info.$field && !$self.$field
+		$gl.count($info.$field != $self.$field);
+		if $info.$field != $self.$field
 		{
-			unsafe { $gl.enable(glow::$gl_name); }
-			$self.$field = true;
+			if $info.$field { unsafe { $gl.context().enable(glow::$gl_name); } }
+			else { unsafe { $gl.context().disable(glow::$gl_name); } }
+			$self.$field = $info.$field;
 		}
-		if !$info.$field && $self.$field
+	}
+}
+
+macro_rules! gl_set
+{
+	($gl: ident, $info: ident, $self: expr, $field: ident, |$value: pat_param| $set: expr) =>
+	{
+		$gl.count($info.$field != $self.$field);
+		if $info.$field != $self.$field
+		{
+			let $value = $info.$field;
+			unsafe { $set; }
+			$self.$field = $info.$field;
+		}
+	}
+}
+
+//Option fields: Some enables the capability and sets the state, $set brings its own unsafe
+macro_rules! gl_able_with
+{
+	($gl: ident, $info: ident, $self: expr, $field: ident, $gl_name: ident, |$value: ident| $set: expr) =>
+	{
+		$gl.count($info.$field != $self.$field);
+		match ($info.$field, $self.$field)
 		{
-			unsafe { $gl.disable(glow::$gl_name); }
-			$self.$field = false;
+			(Some(_), None) => unsafe { $gl.context().enable(glow::$gl_name); },
+			(None, Some(_)) => unsafe { $gl.context().disable(glow::$gl_name); },
+			_ => {}
 		}
+		if let Some($value) = $info.$field { if $self.$field != Some($value) { $set; } }
+		$self.$field = $info.$field;
 	}
 }
 
+#[derive(Clone, Copy)]
 pub struct RenderPassInfo
 {
 	pub clear_color: Option<(f32, f32, f32)>,
-	pub clear_depth: bool
+	pub clear_depth: bool,
+	pub clear_stencil: Option<i32>,
+	pub viewport: Option<(u32, u32, u32, u32)> //(x, y, width, height) with the lower left corner, None for the whole target, clears only affect the viewport
 }
 
-#[derive(Clone, Copy)]
+impl RenderPassInfo
+{
+	//clears nothing and renders to the whole target
+	pub const DEFAULT: Self = Self
+	{
+		clear_color: None,
+		clear_depth: false,
+		clear_stencil: None,
+		viewport: None
+	};
+}
+
+impl Default for RenderPassInfo
+{
+	#[inline]
+	fn default() -> Self
+	{
+		Self::DEFAULT
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct PipelineInfo
 {
 	pub depth_test: bool,
-	pub alpha_blend: bool,
-	pub face_cull: bool
+	pub depth_func: CompareFunc,
+	pub depth_write: bool,
+	pub color_write: (bool, bool, bool, bool),
+	pub blend: Option<BlendMode>, //None disables blending
+	pub face_cull: bool,
+	pub cull_face: CullFace,
+	pub front_face: FrontFace,
+	pub polygon_offset: Option<(f32, f32)>, //(factor, units)
+	pub line_width: f32, //clamped to Capabilities::line_width_range
+	pub stencil: Option<StencilInfo> //None disables the stencil test
+}
+
+impl PipelineInfo
+{
+	//the state after Gl creation
+	pub const DEFAULT: Self = Self
+	{
+		depth_test: true,
+		depth_func: CompareFunc::LessEqual,
+		depth_write: true,
+		color_write: (true, true, true, true),
+		blend: None,
+		face_cull: true,
+		cull_face: CullFace::Back,
+		front_face: FrontFace::CounterClockwise,
+		polygon_offset: None,
+		line_width: 1.0,
+		stencil: None
+	};
+}
+
+impl Default for PipelineInfo
+{
+	#[inline]
+	fn default() -> Self
+	{
+		Self::DEFAULT
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CompareFunc
+{
+	Never,
+	Less,
+	Equal,
+	LessEqual,
+	Greater,
+	NotEqual,
+	GreaterEqual,
+	Always
+}
+
+impl CompareFunc
+{
+	const fn gl_name(&self) -> u32
+	{
+		match self
+		{
+			Self::Never => glow::NEVER,
+			Self::Less => glow::LESS,
+			Self::Equal => glow::EQUAL,
+			Self::LessEqual => glow::LEQUAL,
+			Self::Greater => glow::GREATER,
+			Self::NotEqual => glow::NOTEQUAL,
+			Self::GreaterEqual => glow::GEQUAL,
+			Self::Always => glow::ALWAYS
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CullFace
+{
+	Front,
+	Back,
+	FrontAndBack
+}
+
+impl CullFace
+{
+	const fn gl_name(&self) -> u32
+	{
+		match self
+		{
+			Self::Front => glow::FRONT,
+			Self::Back => glow::BACK,
+			Self::FrontAndBack => glow::FRONT_AND_BACK
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FrontFace
+{
+	CounterClockwise,
+	Clockwise
+}
+
+impl FrontFace
+{
+	const fn gl_name(&self) -> u32
+	{
+		match self
+		{
+			Self::CounterClockwise => glow::CCW,
+			Self::Clockwise => glow::CW
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct StencilInfo
+{
+	pub front: StencilFace,
+	pub back: StencilFace
+}
+
+impl StencilInfo
+{
+	//same configuration for front and back faces
+	#[inline]
+	pub const fn both(face: StencilFace) -> Self
+	{
+		Self { front: face, back: face }
+	}
+
+	fn apply(&self, gl: &Context)
+	{
+		for (face, info) in [(glow::FRONT, &self.front), (glow::BACK, &self.back)]
+		{
+			unsafe
+			{
+				gl.stencil_func_separate(face, info.func.gl_name(), info.reference, info.read_mask);
+				gl.stencil_mask_separate(face, info.write_mask);
+				gl.stencil_op_separate(face, info.fail.gl_name(), info.depth_fail.gl_name(), info.pass.gl_name());
+			}
+		}
+	}
+}
+
+//func(reference & read_mask, stencil & read_mask) decides the test
+#[derive(Clone, Copy, PartialEq)]
+pub struct StencilFace
+{
+	pub func: CompareFunc,
+	pub reference: i32,
+	pub read_mask: u32,
+	pub write_mask: u32,
+	pub fail: StencilOp, //stencil test fails
+	pub depth_fail: StencilOp, //stencil test passes, depth test fails
+	pub pass: StencilOp //both pass
+}
+
+impl StencilFace
+{
+	//passes everything and writes nothing
+	pub const DEFAULT: Self = Self { func: CompareFunc::Always, reference: 0, read_mask: !0, write_mask: !0, fail: StencilOp::Keep, depth_fail: StencilOp::Keep, pass: StencilOp::Keep };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StencilOp
+{
+	Keep,
+	Zero,
+	Replace,
+	Increment,
+	IncrementWrap,
+	Decrement,
+	DecrementWrap,
+	Invert
+}
+
+impl StencilOp
+{
+	const fn gl_name(&self) -> u32
+	{
+		match self
+		{
+			Self::Keep => glow::KEEP,
+			Self::Zero => glow::ZERO,
+			Self::Replace => glow::REPLACE,
+			Self::Increment => glow::INCR,
+			Self::IncrementWrap => glow::INCR_WRAP,
+			Self::Decrement => glow::DECR,
+			Self::DecrementWrap => glow::DECR_WRAP,
+			Self::Invert => glow::INVERT
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlendMode
+{
+	Alpha,
+	PremultipliedAlpha,
+	Additive,
+	Multiply,
+	Custom { color: BlendFunction, alpha: BlendFunction }
+}
+
+impl BlendMode
+{
+	//(color, alpha)
+	pub const fn functions(&self) -> (BlendFunction, BlendFunction)
+	{
+		const fn function(src: BlendFactor, dst: BlendFactor) -> BlendFunction { BlendFunction { equation: BlendEquation::Add, src, dst } }
+		match self
+		{
+			Self::Alpha => (function(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha), function(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha)),
+			Self::PremultipliedAlpha => (function(BlendFactor::One, BlendFactor::OneMinusSrcAlpha), function(BlendFactor::One, BlendFactor::OneMinusSrcAlpha)),
+			Self::Additive => (function(BlendFactor::One, BlendFactor::One), function(BlendFactor::One, BlendFactor::One)),
+			Self::Multiply => (function(BlendFactor::DstColor, BlendFactor::Zero), function(BlendFactor::DstAlpha, BlendFactor::Zero)),
+			Self::Custom { color, alpha } => (*color, *alpha)
+		}
+	}
+
+	pub(crate) fn apply(&self, gl: &Context)
+	{
+		let (color, alpha) = self.functions();
+		unsafe
+		{
+			gl.blend_equation_separate(color.equation.gl_name(), alpha.equation.gl_name());
+			gl.blend_func_separate(color.src.gl_name(), color.dst.gl_name(), alpha.src.gl_name(), alpha.dst.gl_name());
+		}
+	}
+}
+
+//result = equation(src * source, dst * destination)
+#[derive(Clone, Copy, PartialEq)]
+pub struct BlendFunction
+{
+	pub equation: BlendEquation,
+	pub src: BlendFactor,
+	pub dst: BlendFactor
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlendEquation
+{
+	Add,
+	Subtract,
+	ReverseSubtract
+}
+
+impl BlendEquation
+{
+	const fn gl_name(&self) -> u32
+	{
+		match self
+		{
+			Self::Add => glow::FUNC_ADD,
+			Self::Subtract => glow::FUNC_SUBTRACT,
+			Self::ReverseSubtract => glow::FUNC_REVERSE_SUBTRACT
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlendFactor
+{
+	Zero,
+	One,
+	SrcColor,
+	OneMinusSrcColor,
+	DstColor,
+	OneMinusDstColor,
+	SrcAlpha,
+	OneMinusSrcAlpha,
+	DstAlpha,
+	OneMinusDstAlpha,
+	SrcAlphaSaturate //only as src
+}
+
+impl BlendFactor
+{
+	const fn gl_name(&self) -> u32
+	{
+		match self
+		{
+			Self::Zero => glow::ZERO,
+			Self::One => glow::ONE,
+			Self::SrcColor => glow::SRC_COLOR,
+			Self::OneMinusSrcColor => glow::ONE_MINUS_SRC_COLOR,
+			Self::DstColor => glow::DST_COLOR,
+			Self::OneMinusDstColor => glow::ONE_MINUS_DST_COLOR,
+			Self::SrcAlpha => glow::SRC_ALPHA,
+			Self::OneMinusSrcAlpha => glow::ONE_MINUS_SRC_ALPHA,
+			Self::DstAlpha => glow::DST_ALPHA,
+			Self::OneMinusDstAlpha => glow::ONE_MINUS_DST_ALPHA,
+			Self::SrcAlphaSaturate => glow::SRC_ALPHA_SATURATE
+		}
+	}
 }
 
 #[derive(Clone, Copy)]
 pub enum Primitives
 {
-	Points,
+	Points, //size from gl_PointSize
 	Lines,
 	LineStrip,
+	LineLoop,
 	Triangles,
+	TriangleStrip,
 	TriangleFan
 }
 
@@ -51,12 +397,21 @@ impl Primitives
 			Self::Points => glow::POINTS,
 			Self::Lines => glow::LINES,
 			Self::LineStrip => glow::LINE_STRIP,
+			Self::LineLoop => glow::LINE_LOOP,
 			Self::Triangles => glow::TRIANGLES,
+			Self::TriangleStrip => glow::TRIANGLE_STRIP,
 			Self::TriangleFan => glow::TRIANGLE_FAN
 		}
 	}
 }
 
+//the first count elements of buffer, see Pipeline::draw_instanced
+pub struct Instances<'a, I: AttributesReprCpacked>
+{
+	pub buffer: &'a VertexBuffer<I>,
+	pub count: u32
+}
+
 impl Gl
 {
 	#[inline]
@@ -65,49 +420,164 @@ impl Gl
 		let gl = &self.raw;
 		let (width, height) = match &render_target
 		{
-			RenderTarget::Screen => (self.window_dims.0 as i32, self.window_dims.1 as i32),
+			RenderTarget::Screen =>
+			{
+				unsafe { gl.bind_framebuffer(glow::FRAMEBUFFER, None); }
+				(self.window_dims.0 as i32, self.window_dims.1 as i32)
+			},
 			RenderTarget::Texture(framebuffer) =>
 			{
 				unsafe { gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer.framebuffer)); }
-				(framebuffer.size() as i32, framebuffer.size() as i32)
+				(framebuffer.width() as i32, framebuffer.height() as i32)
 			}
 		};
-		if (width, height) != self.viewport
+		let viewport = match info.viewport
+		{
+			Some((x, y, w, h)) =>
+			{
+				if x as i32 + w as i32 > width || y as i32 + h as i32 > height { panic!("Gl::render_pass: The viewport exceeds the render target."); }
+				(x as i32, y as i32, w as i32, h as i32)
+			},
+			None => (0, 0, width, height)
+		};
+		if viewport != self.viewport
 		{
-			unsafe { gl.viewport(0, 0, width, height); }
-			self.viewport = (width, height);
+			unsafe { gl.context().viewport(viewport.0, viewport.1, viewport.2, viewport.3); }
+			self.viewport = viewport;
 		}
+		//a previous pass may have left a scissor, partial viewports clip the clear
+		self.scissor(if info.viewport.is_some() && (info.clear_color.is_some() || info.clear_depth || info.clear_stencil.is_some()) { Some(viewport) } else { None });
+		let gl = &self.raw;
+		//glClear respects the write masks
+		if info.clear_color.is_some() && self.pipeline.color_write != (true, true, true, true)
+		{
+			unsafe { gl.context().color_mask(true, true, true, true); }
+			self.pipeline.color_write = (true, true, true, true);
+		}
+		if info.clear_depth && !self.pipeline.depth_write
+		{
+			unsafe { gl.context().depth_mask(true); }
+			self.pipeline.depth_write = true;
+		}
+		if info.clear_stencil.is_some()
+		{
+			//the masks outlive a disabled stencil test, so they are not tracked
+			unsafe { gl.context().stencil_mask(!0); }
+			if let Some(stencil) = &mut self.pipeline.stencil
+			{
+				stencil.front.write_mask = !0;
+				stencil.back.write_mask = !0;
+			}
+		}
+		let mut mask = 0;
 		if let Some(clear_color) = info.clear_color
 		{
-			if Some(clear_color) != Some(self.clear_color)
+			if clear_color != self.clear_color
 			{
-				unsafe { gl.clear_color(clear_color.0, clear_color.1, clear_color.2, 1.0); }
+				unsafe { gl.context().clear_color(clear_color.0, clear_color.1, clear_color.2, 1.0); }
 				self.clear_color = clear_color;
 			}
-			unsafe { gl.clear(if info.clear_depth { glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT } else { glow::COLOR_BUFFER_BIT }); }
-		} else if info.clear_depth { unsafe { gl.clear(glow::DEPTH_BUFFER_BIT); } }
-		RenderPass { gl: self, render_target }
+			mask |= glow::COLOR_BUFFER_BIT;
+		}
+		if info.clear_depth { mask |= glow::DEPTH_BUFFER_BIT; }
+		if let Some(clear_stencil) = info.clear_stencil
+		{
+			if clear_stencil != self.clear_stencil
+			{
+				unsafe { gl.context().clear_stencil(clear_stencil); }
+				self.clear_stencil = clear_stencil;
+			}
+			mask |= glow::STENCIL_BUFFER_BIT;
+		}
+		if mask != 0 { unsafe { gl.context().clear(mask); } }
+		self.scissor(None);
+		RenderPass { gl: self, _render_target: PhantomData }
+	}
+
+	fn scissor(&mut self, scissor: Option<(i32, i32, i32, i32)>)
+	{
+		match (scissor, self.scissor)
+		{
+			(Some(_), None) => unsafe { self.raw.context().enable(glow::SCISSOR_TEST); },
+			(None, Some(_)) => unsafe { self.raw.context().disable(glow::SCISSOR_TEST); },
+			_ => {}
+		}
+		if let Some((x, y, width, height)) = scissor && self.scissor != scissor { unsafe { self.raw.context().scissor(x, y, width, height); } }
+		self.scissor = scissor;
+	}
+}
+
+impl Gl
+{
+	//(x, y) is the lower left corner, the RGBA rows are returned from top to bottom
+	pub fn read_pixels(&mut self, x: u32, y: u32, width: u32, height: u32) -> Vec<u8>
+	{
+		if x + width > self.window_dims.0 || y + height > self.window_dims.1 { panic!("Gl::read_pixels: Region exceeds the window size."); }
+		Self::read_pixels_raw(&self.raw, None, x, y, width, height)
+	}
+
+	//restores the framebuffer binding, so it can be called during a render pass
+	pub(crate) fn read_pixels_raw(gl: &Raw, framebuffer: Option<<Context as HasContext>::Framebuffer>, x: u32, y: u32, width: u32, height: u32) -> Vec<u8>
+	{
+		let row = width as usize * 4;
+		let mut data = vec![0; row * height as usize];
+		let bound = gl.framebuffer();
+		unsafe
+		{
+			gl.bind_framebuffer(glow::FRAMEBUFFER, framebuffer);
+			gl.context().read_pixels(x as i32, y as i32, width as i32, height as i32, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelPackData::Slice(&mut data));
+			gl.bind_framebuffer(glow::FRAMEBUFFER, bound);
+		}
+		let mut flipped = Vec::with_capacity(data.len());
+		for line in data.chunks_exact(row).rev() { flipped.extend_from_slice(line); }
+		flipped
 	}
 }
 
 impl<'a, 'b> RenderPass<'a, 'b>
 {
+	//(x, y) is the lower left corner in render target pixels, lasts until clear_scissor or the end of the pass
 	#[inline]
-	pub fn pipeline<'c, 'd, T: AttributesReprCpacked>(&'c mut self, shader: &'d Shader<T>, info: PipelineInfo) -> Pipeline<'c, 'd, T>
+	pub fn set_scissor(&mut self, x: u32, y: u32, width: u32, height: u32)
+	{
+		self.gl.scissor(Some((x as i32, y as i32, width as i32, height as i32)));
+	}
+
+	#[inline]
+	pub fn clear_scissor(&mut self)
+	{
+		self.gl.scissor(None);
+	}
+
+	#[inline]
+	pub fn pipeline<'c, 'd, T: VertexStreams, I: AttributesReprCpacked>(&'c mut self, shader: &'d Shader<T, I>, info: PipelineInfo) -> Pipeline<'c, 'd, T, I>
 	{
 		let gl = &self.gl.raw;
 		gl_able!(gl, info, self.gl.pipeline, depth_test, DEPTH_TEST);
-		gl_able!(gl, info, self.gl.pipeline, alpha_blend, BLEND);
+		gl_set!(gl, info, self.gl.pipeline, depth_func, |func| gl.context().depth_func(func.gl_name()));
+		gl_set!(gl, info, self.gl.pipeline, depth_write, |write| gl.context().depth_mask(write));
+		gl_set!(gl, info, self.gl.pipeline, color_write, |(r, g, b, a)| gl.context().color_mask(r, g, b, a));
+		gl_able_with!(gl, info, self.gl.pipeline, blend, BLEND, |blend| if blend.functions() != self.gl.blend_functions
+		{
+			blend.apply(gl.context());
+			self.gl.blend_functions = blend.functions();
+		});
 		gl_able!(gl, info, self.gl.pipeline, face_cull, CULL_FACE);
+		gl_set!(gl, info, self.gl.pipeline, cull_face, |face| gl.context().cull_face(face.gl_name()));
+		gl_set!(gl, info, self.gl.pipeline, front_face, |face| gl.context().front_face(face.gl_name()));
+		gl_able_with!(gl, info, self.gl.pipeline, polygon_offset, POLYGON_OFFSET_FILL, |offset| unsafe { gl.context().polygon_offset(offset.0, offset.1) });
+		gl_able_with!(gl, info, self.gl.pipeline, stencil, STENCIL_TEST, |stencil| stencil.apply(gl.context()));
+		let range = self.gl.capabilities.line_width_range;
+		gl_set!(gl, info, self.gl.pipeline, line_width, |width| gl.context().line_width(width.clamp(range.0, range.1)));
 		unsafe { gl.use_program(Some(shader.program)); }
-		Pipeline { gl: &mut self.gl, shader, texture_active: 0, texture_lock: 0, texture_used: false }
+		Pipeline { gl: &mut self.gl, shader }
 	}
 }
 
-impl<'a, 'b, T: AttributesReprCpacked> Pipeline<'a, 'b, T>
+impl<'a, 'b, T: VertexStreams, I: AttributesReprCpacked> Pipeline<'a, 'b, T, I>
 {
 	#[inline]
-	pub fn uniform_name<U: UniformType>(&mut self, name: &str, value: &U) -> &mut Self
+	pub fn uniform_name<U: UniformType + ?Sized>(&mut self, name: &str, value: &U) -> &mut Self
 	{
 		let key = self.shader.get_key(name);
 		unsafe { value.set(self, &key); }
@@ -115,7 +585,7 @@ impl<'a, 'b, T: AttributesReprCpacked> Pipeline<'a, 'b, T>
 	}
 
 	#[inline]
-	pub fn uniform_key<U: UniformType>(&mut self, key: &UniformKey<U>, value: &U) -> &mut Self
+	pub fn uniform_key<U: UniformType + ?Sized>(&mut self, key: &UniformKey<U>, value: &U) -> &mut Self
 	{
 		if DEBUG && self.shader.id != key.shader_id
 		{
@@ -127,60 +597,139 @@ impl<'a, 'b, T: AttributesReprCpacked> Pipeline<'a, 'b, T>
 		self
 	}
 
+	//sets "name.field" for every field, name can be an array element like "lights[2]"
 	#[inline]
-	pub fn draw(&mut self, primitives: Primitives, vertices: &VertexBuffer<T>, indices: Option<&IndexBuffer>, offset: u32, count: u32)
+	pub fn uniform_struct<U: UniformStruct>(&mut self, name: &str, value: &U) -> &mut Self
+	{
+		value.set_uniforms(self, name);
+		self
+	}
+
+	//unit: assigned to the sampler at shader creation
+	pub(crate) fn bind_texture(&mut self, unit: u32, target: u32, texture: <Context as HasContext>::Texture)
 	{
-		let gl = &self.gl.raw;
 		unsafe
 		{
-			gl.bind_buffer(glow::ARRAY_BUFFER, Some(vertices.buffer));
+			self.gl.raw.active_texture(glow::TEXTURE0 + unit);
+			self.gl.raw.bind_texture(target, Some(texture));
+		}
+	}
 
-			for (ty, location, offset) in &self.shader.attributes
+	//one buffer per stream in the order of T, the first one holds the vertex array objects
+	pub fn draw_streams(&mut self, primitives: Primitives, vertices: &[&RawVertexBuffer], indices: Option<&RawIndexBuffer>, offset: u32, count: u32)
+	{
+		if vertices.len() != T::STREAMS.len() { panic!("Pipeline::draw_streams: The Shader expects {} vertex streams.", T::STREAMS.len()); }
+		if vertices.iter().zip(T::STREAMS).any(|(vertices, stream)| vertices.attributes != *stream) { panic!("Pipeline::draw_streams: The vertex buffers do not match the streams of the Shader."); }
+		self.draw_raw(primitives, vertices, None, indices, offset, count);
+	}
+
+	fn draw_raw(&mut self, primitives: Primitives, vertices: &[&RawVertexBuffer], instances: Option<(&RawVertexBuffer, u32)>, indices: Option<&RawIndexBuffer>, offset: u32, count: u32)
+	{
+		match indices
+		{
+			None => if vertices.iter().any(|vertices| offset + count > vertices.length) { panic!("Pipeline::draw: Not enough vertices in buffer."); },
+			Some(indices) => if offset + count > indices.length { panic!("Pipeline::draw: Not enough indices in buffer."); }
+		}
+		let gl = &self.gl.raw;
+		let vao = self.gl.capabilities.vertex_array_object;
+		if vao
+		{
+			//the attribute setup and the index buffer binding are recorded once
+			let mut streams = [None; 4];
+			for (stream, vertices) in streams.iter_mut().zip(vertices) { *stream = Some(vertices.id); }
+			let key = VaoKey { shader: self.shader.id, streams, indices: indices.map(|indices| indices.id), instances: instances.map(|(instances, _)| instances.id) };
+			match gl.vertex_array(&key)
 			{
-				match ty
+				Some(vao) => unsafe { gl.bind_vertex_array(Some(vao)); },
+				None =>
 				{
-					BufferType::Float { size } => gl.vertex_attrib_pointer_f32(*location, *size as i32, glow::FLOAT, false, std::mem::size_of::<T>() as i32, *offset),
-					#[cfg(not(target_arch = "wasm32"))]
-					BufferType::Int { signed, size } => gl.vertex_attrib_pointer_i32(*location, *size as i32, if *signed { glow::INT } else { glow::UNSIGNED_INT }, std::mem::size_of::<T>() as i32, *offset)
+					let vao = unsafe { gl.context().create_vertex_array() }.unwrap();
+					unsafe { gl.bind_vertex_array(Some(vao)); }
+					self.bind_attributes(vertices, instances, indices);
+					gl.insert_vertex_array(key, vao);
 				}
-				gl.enable_vertex_attrib_array(*location);
 			}
-
+		}
+		else { self.bind_attributes(vertices, instances, indices); }
+		unsafe
+		{
 			match indices
 			{
-				None =>
+				None => match instances
 				{
-					if offset + count > vertices.length { panic!("Pipeline::draw: Not enough vertices in buffer."); }
-					gl.draw_arrays(primitives.gl_name(), offset as i32, count as i32);
+					None => gl.context().draw_arrays(primitives.gl_name(), offset as i32, count as i32),
+					Some((_, instance_count)) => gl.context().draw_arrays_instanced(primitives.gl_name(), offset as i32, count as i32, instance_count as i32)
 				},
-				Some(indices) =>
+				Some(indices) => match instances
 				{
-					if offset + count > indices.length { panic!("Pipeline::draw: Not enough indices in buffer."); }
-					gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(indices.buffer));
-					gl.draw_elements(primitives.gl_name(), count as i32, glow::UNSIGNED_SHORT, offset as i32);
-					gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
+					None => gl.context().draw_elements(primitives.gl_name(), count as i32, indices.code, (offset * indices.size) as i32),
+					Some((_, instance_count)) => gl.context().draw_elements_instanced(primitives.gl_name(), count as i32, indices.code, (offset * indices.size) as i32, instance_count as i32)
 				}
 			}
-			
-			for (_, location, _) in &self.shader.attributes { gl.disable_vertex_attrib_array(*location); }
 
-			gl.bind_buffer(glow::ARRAY_BUFFER, None);
+			//the vertex array stays bound, see Raw::bind_vertex_array
+			if !vao
+			{
+				for (_, location, _) in self.shader.streams.iter().flatten() { gl.context().disable_vertex_attrib_array(*location); }
+				if instances.is_some()
+				{
+					for (_, location, _) in &self.shader.instance_attributes
+					{
+						gl.context().vertex_attrib_divisor(*location, 0);
+						gl.context().disable_vertex_attrib_array(*location);
+					}
+				}
+			}
 		}
 	}
-}
 
-impl<T: AttributesReprCpacked> Drop for Pipeline<'_, '_, T>
-{
-	#[inline]
-	fn drop(&mut self)
+	fn bind_attributes(&self, vertices: &[&RawVertexBuffer], instances: Option<(&RawVertexBuffer, u32)>, indices: Option<&RawIndexBuffer>)
 	{
 		let gl = &self.gl.raw;
+		for (vertices, attributes) in vertices.iter().zip(&self.shader.streams) { Self::enable_attributes(gl, vertices.buffer, attributes, vertices.stride as i32, 0); }
+		if let Some((instances, _)) = instances { Self::enable_attributes(gl, instances.buffer, &self.shader.instance_attributes, instances.stride as i32, 1); }
 		unsafe
 		{
-			if self.texture_used { gl.bind_texture(glow::TEXTURE_2D, None); }
-			gl.use_program(None);
+			if let Some(indices) = indices { gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(indices.buffer)); }
 		}
 	}
+
+	fn enable_attributes(gl: &Raw, buffer: <Context as HasContext>::Buffer, attributes: &[(BufferType, u32, i32)], stride: i32, divisor: u32)
+	{
+		unsafe
+		{
+			gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+			for (ty, location, offset) in attributes
+			{
+				match ty
+				{
+					#[cfg(not(target_arch = "wasm32"))]
+					BufferType::Int { .. } => gl.context().vertex_attrib_pointer_i32(*location, ty.size() as i32, ty.data_type(), stride, *offset),
+					_ => gl.context().vertex_attrib_pointer_f32(*location, ty.size() as i32, ty.data_type(), ty.normalized(), stride, *offset)
+				}
+				gl.context().enable_vertex_attrib_array(*location);
+				if divisor != 0 { gl.context().vertex_attrib_divisor(*location, divisor); }
+			}
+		}
+	}
+}
+
+impl<T: AttributesReprCpacked, I: AttributesReprCpacked> Pipeline<'_, '_, T, I>
+{
+	#[inline]
+	pub fn draw(&mut self, primitives: Primitives, vertices: &VertexBuffer<T>, indices: Option<&RawIndexBuffer>, offset: u32, count: u32)
+	{
+		self.draw_raw(primitives, &[vertices], None, indices, offset, count);
+	}
+
+	//needs ANGLE_instanced_arrays on WebGL 1 and OpenGL 3.3 or ARB_instanced_arrays on desktop
+	#[inline]
+	pub fn draw_instanced(&mut self, primitives: Primitives, vertices: &VertexBuffer<T>, instances: Instances<I>, indices: Option<&RawIndexBuffer>, offset: u32, count: u32)
+	{
+		if !self.gl.capabilities.instancing { panic!("Pipeline::draw_instanced: Instancing is not supported."); }
+		if instances.count > instances.buffer.length { panic!("Pipeline::draw_instanced: Not enough instances in buffer."); }
+		self.draw_raw(primitives, &[vertices], Some((instances.buffer, instances.count)), indices, offset, count);
+	}
 }
 
 impl Drop for RenderPass<'_, '_>
@@ -188,6 +737,6 @@ impl Drop for RenderPass<'_, '_>
 	#[inline]
 	fn drop(&mut self)
 	{
-		if let RenderTarget::Texture(_) = self.render_target { unsafe { self.gl.raw.bind_framebuffer(glow::FRAMEBUFFER, None); } }
+		self.gl.scissor(None);
 	}
 }
@@ -3,257 +3,765 @@ use gru_misc::math::*;
 
 impl Gl
 {
-	pub fn new_shader<T: AttributesReprCpacked>(&mut self, vertex_glsl: &str, fragment_glsl: &str) -> Shader<T>
+	#[inline]
+	pub fn new_shader<T: VertexStreams>(&mut self, vertex_glsl: &str, fragment_glsl: &str) -> Shader<T>
+	{
+		self.new_instanced_shader(vertex_glsl, fragment_glsl)
+	}
+
+	pub fn new_instanced_shader<T: VertexStreams, I: AttributesReprCpacked>(&mut self, vertex_glsl: &str, fragment_glsl: &str) -> Shader<T, I>
+	{
+		Self::expect_shader(self.try_new_instanced_shader(vertex_glsl, fragment_glsl))
+	}
+
+	#[inline]
+	pub fn try_new_shader<T: VertexStreams>(&mut self, vertex_glsl: &str, fragment_glsl: &str) -> Result<Shader<T>, ShaderError>
+	{
+		self.try_new_instanced_shader(vertex_glsl, fragment_glsl)
+	}
+
+	#[inline]
+	pub fn try_new_instanced_shader<T: VertexStreams, I: AttributesReprCpacked>(&mut self, vertex_glsl: &str, fragment_glsl: &str) -> Result<Shader<T, I>, ShaderError>
+	{
+		self.try_new_shader_with(&GlslPreprocessor::new(), vertex_glsl, fragment_glsl)
+	}
+
+	pub fn new_shader_with<T: VertexStreams, I: AttributesReprCpacked>(&mut self, preprocessor: &GlslPreprocessor, vertex_glsl: &str, fragment_glsl: &str) -> Shader<T, I>
+	{
+		Self::expect_shader(self.try_new_shader_with(preprocessor, vertex_glsl, fragment_glsl))
+	}
+
+	pub fn try_new_shader_with<T: VertexStreams, I: AttributesReprCpacked>(&mut self, preprocessor: &GlslPreprocessor, vertex_glsl: &str, fragment_glsl: &str) -> Result<Shader<T, I>, ShaderError>
+	{
+		let vertex_source = preprocessor.process(ShaderStage::Vertex, self.glsl_vertex_header, vertex_glsl)?;
+		let fragment_source = preprocessor.process(ShaderStage::Fragment, self.glsl_fragment_header, fragment_glsl)?;
+		self.link_shader(&vertex_source, &fragment_source)
+	}
+
+	pub fn new_shader_variants<T: VertexStreams, I: AttributesReprCpacked>(&mut self, preprocessor: &GlslPreprocessor, vertex_glsl: &str, fragment_glsl: &str, flags: &[&str]) -> ShaderVariants<T, I>
+	{
+		Self::expect_shader(self.try_new_shader_variants(preprocessor, vertex_glsl, fragment_glsl, flags))
+	}
+
+	//compiles all 2^flags.len() permutations, every enabled flag is #defined
+	pub fn try_new_shader_variants<T: VertexStreams, I: AttributesReprCpacked>(&mut self, preprocessor: &GlslPreprocessor, vertex_glsl: &str, fragment_glsl: &str, flags: &[&str]) -> Result<ShaderVariants<T, I>, ShaderError>
+	{
+		if flags.len() > 8 { return Err(ShaderError::link("At most 8 flags are supported.".to_string())); }
+		let mut shaders = Vec::with_capacity(1 << flags.len());
+		for mask in 0..(1u32 << flags.len())
+		{
+			let mut preprocessor = preprocessor.clone();
+			for (i, flag) in flags.iter().enumerate() { if mask & (1 << i) != 0 { preprocessor.define(flag, ""); } }
+			shaders.push(self.try_new_shader_with(&preprocessor, vertex_glsl, fragment_glsl)?);
+		}
+		Ok(ShaderVariants { flags: flags.iter().map(|flag| flag.to_string()).collect(), shaders })
+	}
+
+	fn expect_shader<S>(result: Result<S, ShaderError>) -> S
+	{
+		match result
+		{
+			Ok(shader) => shader,
+			Err(err) =>
+			{
+				log(&format!("{}", err));
+				panic!("{}", err);
+			}
+		}
+	}
+
+	fn link_shader<T: VertexStreams, I: AttributesReprCpacked>(&mut self, vertex_source: &GlslSource, fragment_source: &GlslSource) -> Result<Shader<T, I>, ShaderError>
 	{
 		let gl = &self.raw;
-		let program = unsafe { gl.create_program() }.unwrap();
-		let id = self.shader_id;
-		self.shader_id += 1;
+		let program = unsafe { gl.context().create_program() }.unwrap();
 		//shader
-		let vertex_shader = unsafe
+		let vertex_shader = match Self::compile_shader(gl.context(), ShaderStage::Vertex, vertex_source)
 		{
-			let source = format!("{}\n{}", self.glsl_vertex_header, vertex_glsl);
-			let shader = gl.create_shader(glow::VERTEX_SHADER).unwrap();
-			gl.shader_source(shader, &source);
-			gl.compile_shader(shader);
-			if !gl.get_shader_compile_status(shader)
+			Ok(shader) => shader,
+			Err(err) =>
 			{
-				let info = gl.get_shader_info_log(shader);
-				log(&source);
-				log(&info);
-				panic!("{}", info);
+				unsafe { gl.delete_program(program); }
+				return Err(err);
 			}
-			gl.attach_shader(program, shader);
-			shader
 		};
-		let fragment_shader = unsafe
+		let fragment_shader = match Self::compile_shader(gl.context(), ShaderStage::Fragment, fragment_source)
 		{
-			let source = format!("{}\n{}", self.glsl_fragment_header, fragment_glsl);
-			let shader = gl.create_shader(glow::FRAGMENT_SHADER).unwrap();
-			gl.shader_source(shader, &source);
-			gl.compile_shader(shader);
-			if !gl.get_shader_compile_status(shader)
+			Ok(shader) => shader,
+			Err(err) =>
 			{
-				let info = gl.get_shader_info_log(shader);
-				log(&source);
-				log(&info);
-				panic!("{}", info);
+				unsafe
+				{
+					gl.context().delete_shader(vertex_shader);
+					gl.delete_program(program);
+				}
+				return Err(err);
 			}
-			gl.attach_shader(program, shader);
-			shader
 		};
 		let mut attributes = Vec::new();
 		let mut uniforms = AHashMap::new();
-		unsafe
+		let result = unsafe
 		{
-			//1. link
-			gl.link_program(program);
-	        if !gl.get_program_link_status(program)
-			{
-				let info = gl.get_program_info_log(program);
-				log(&info);
-				panic!("{}", info);
-			}
-	        //extract attributes
-			let len = gl.get_active_attributes(program);
-			for i in 0..len
+			gl.context().attach_shader(program, vertex_shader);
+			gl.context().attach_shader(program, fragment_shader);
+			let result = (||
 			{
-				let attribute = gl.get_active_attribute(program, i).unwrap();
-				Self::attribute_location(&mut self.attributes, &attribute.name, &mut |name, location|
+				//1. link
+				gl.context().link_program(program);
+				if !gl.context().get_program_link_status(program) { return Err(ShaderError::link(gl.context().get_program_info_log(program))); }
+				//extract attributes
+				let len = gl.context().get_active_attributes(program);
+				for i in 0..len
 				{
-					attributes.push((name.to_string(), attribute.atype));
-					gl.bind_attrib_location(program, location, name);
-				});
-			}
-			//validate attributes
-			if T::ATTRIBUTES.len() != attributes.len() { log("Wrong number of attributes."); } //no panic due to nVidia attribute elision
-			for (buffer_type, name) in T::ATTRIBUTES
-			{
-				match attributes.iter().find(|attr| &attr.0 == name)
+					let attribute = gl.context().get_active_attribute(program, i).unwrap();
+					Self::attribute_location(&mut self.attributes, &attribute.name, &mut |name, location|
+					{
+						attributes.push((name.to_string(), attribute.atype));
+						gl.context().bind_attrib_location(program, location, name);
+					});
+				}
+				//validate attributes
+				let vertex_attributes = || T::STREAMS.iter().flat_map(|stream| stream.iter());
+				if vertex_attributes().count() + I::ATTRIBUTES.len() != attributes.len() { log("Wrong number of attributes."); } //no panic due to nVidia attribute elision
+				for (buffer_type, name) in vertex_attributes().chain(I::ATTRIBUTES)
 				{
-					Some(attr) => if attr.1 != buffer_type.code() { panic!("Wrong attribute type for \"{}\".", attr.0); },
-					None => log(&format!("The Shader is missing attribute \"{}\"", name)) //no panic due to nVidia attribute elision
+					match attributes.iter().find(|attr| &attr.0 == name)
+					{
+						Some(attr) => if attr.1 != buffer_type.code() { return Err(ShaderError::link(format!("Wrong attribute type for \"{}\".", attr.0))); },
+						None => log(&format!("The Shader is missing attribute \"{}\"", name)) //no panic due to nVidia attribute elision
+					}
 				}
-			}
-			//2. link
-			gl.link_program(program);
-	        if !gl.get_program_link_status(program)
-			{
-				let info = gl.get_program_info_log(program);
-				log(&info);
-				panic!("{}", info);
-			}
+				//2. link
+				gl.context().link_program(program);
+				if !gl.context().get_program_link_status(program) { return Err(ShaderError::link(gl.context().get_program_info_log(program))); }
+				Ok(())
+			})();
 			//clean
-			gl.detach_shader(program, vertex_shader);
-	        gl.delete_shader(vertex_shader);
-	        gl.detach_shader(program, fragment_shader);
-	        gl.delete_shader(fragment_shader);
-			//extract uniforms
-			let len = gl.get_active_uniforms(program);
+			gl.context().detach_shader(program, vertex_shader);
+			gl.context().delete_shader(vertex_shader);
+			gl.context().detach_shader(program, fragment_shader);
+			gl.context().delete_shader(fragment_shader);
+			result
+		};
+		if let Err(err) = result
+		{
+			unsafe { gl.delete_program(program); }
+			return Err(err);
+		}
+		unsafe
+		{
+			//extract uniforms, samplers get fixed texture units
+			let len = gl.context().get_active_uniforms(program);
+			let mut units = 0;
 			for i in 0..len
 			{
-				let uniform = gl.get_active_uniform(program, i).unwrap();
-				let location = gl.get_uniform_location(program, &uniform.name).unwrap();
-				uniforms.insert(uniform.name, (location, uniform.utype));
+				let uniform = gl.context().get_active_uniform(program, i).unwrap();
+				let location = gl.context().get_uniform_location(program, &uniform.name).unwrap();
+				let size = uniform.size as u32;
+				let unit = units;
+				if matches!(uniform.utype, glow::SAMPLER_2D | glow::SAMPLER_CUBE)
+				{
+					units += size;
+					//the last unit is reserved for texture updates, see Raw::edit_texture
+					let available = self.capabilities.max_texture_units - 1;
+					if units > available
+					{
+						gl.delete_program(program);
+						return Err(ShaderError::link(format!("The samplers need more than the {} available texture units.", available)));
+					}
+					gl.use_program(Some(program));
+					gl.context().uniform_1_i32_slice(Some(&location), &(unit..units).map(|unit| unit as i32).collect::<Vec<_>>());
+				}
+				//arrays are reflected as "name[0]", make them available as "name" too
+				if let Some(name) = uniform.name.strip_suffix("[0]") { uniforms.insert(name.to_string(), (location.clone(), uniform.utype, size, unit)); }
+				uniforms.insert(uniform.name, (location, uniform.utype, size, unit));
 			}
 		}
+		let id = self.shader_id;
+		self.shader_id += 1;
 		//transform attributes
-		let mut attributes = Vec::with_capacity(T::ATTRIBUTES.len());
+		let streams = T::STREAMS.iter().map(|stream| Self::attribute_layout(&mut self.attributes, stream)).collect();
+		let instance_attributes = Self::attribute_layout(&mut self.attributes, I::ATTRIBUTES);
+		Ok(Shader { gl: gl.clone(), id, program, uniforms, streams, instance_attributes, _phantom: PhantomData })
+	}
+
+	fn compile_shader(gl: &Context, stage: ShaderStage, source: &GlslSource) -> Result<<Context as HasContext>::Shader, ShaderError>
+	{
+		unsafe
+		{
+			let shader = gl.create_shader(stage.code()).unwrap();
+			gl.shader_source(shader, &source.glsl);
+			gl.compile_shader(shader);
+			if gl.get_shader_compile_status(shader) { Ok(shader) }
+			else
+			{
+				let log = gl.get_shader_info_log(shader);
+				gl.delete_shader(shader);
+				Err(ShaderError::compile(stage, log, source))
+			}
+		}
+	}
+
+	fn attribute_layout(locations: &mut AHashMap<String, u32>, attributes: &[(BufferType, &str)]) -> Vec<(BufferType, u32, i32)>
+	{
+		let mut layout = Vec::with_capacity(attributes.len());
 		let mut size_of_t = 0;
-		for (ty, name) in T::ATTRIBUTES
+		for (ty, name) in attributes
 		{
 			let mut location = 0;
-			Self::attribute_location(&mut self.attributes, name, &mut |_, loc| location = loc);
-			attributes.push((*ty, location, size_of_t as i32));
-			size_of_t += (match ty
-			{
-				BufferType::Float { size } => *size,
-				#[cfg(not(target_arch = "wasm32"))]
-				BufferType::Int { size, .. } => *size
-			}) as usize * 4;
+			Self::attribute_location(locations, name, &mut |_, loc| location = loc);
+			layout.push((*ty, location, size_of_t as i32));
+			size_of_t += ty.bytes();
 		}
-        Shader { gl: gl.clone(), id, program, uniforms, attributes, _phantom: PhantomData }
+		layout
 	}
 }
 
-impl<T: AttributesReprCpacked> Shader<T>
+impl<T: VertexStreams, I: AttributesReprCpacked> Shader<T, I>
 {
-	pub fn get_key<U: UniformType>(&self, name: &str) -> UniformKey<U>
+	pub fn get_key<U: UniformType + ?Sized>(&self, name: &str) -> UniformKey<U>
 	{
-		let (location, utype) = self.uniforms.get(name).expect(&format!("The uniform \"{}\" does not exist.", name));
+		let (location, utype, size, unit) = self.uniforms.get(name).expect(&format!("The uniform \"{}\" does not exist.", name));
 		if *utype != U::CODE { panic!("The uniform \"{}\" has the wrong type.", name); }
-		UniformKey { key: location.clone(), shader_id: self.id, _phatom: PhantomData }
+		UniformKey { key: location.clone(), shader_id: self.id, size: *size, unit: *unit, _phatom: PhantomData }
 	}
 }
 
-pub unsafe trait UniformType: Sized
+//#include "name" (see GlslPreprocessor::include and include_file), injected #defines and the #extension lines WebGL 1 needs
+#[derive(Clone, Default)]
+pub struct GlslPreprocessor
+{
+	includes: AHashMap<String, String>,
+	files: AHashMap<String, String>, //by path, see include_path
+	source_path: String,
+	defines: Vec<(String, String)>
+}
+
+//(extension, identifiers that need it) for fragment shaders on WebGL 1
+#[cfg(target_arch = "wasm32")]
+const FRAGMENT_EXTENSIONS: [(&str, &[&str]); 4] =
+[
+	("GL_OES_standard_derivatives", &["dFdx", "dFdy", "fwidth"]),
+	("GL_EXT_shader_texture_lod", &["texture2DLodEXT", "texture2DProjLodEXT", "textureCubeLodEXT", "texture2DGradEXT", "texture2DProjGradEXT", "textureCubeGradEXT"]),
+	("GL_EXT_frag_depth", &["gl_FragDepthEXT"]),
+	("GL_EXT_draw_buffers", &["gl_FragData", "gl_MaxDrawBuffers"]) //multiple Framebuffer color attachments
+];
+
+struct GlslSource
+{
+	glsl: String,
+	prefix: usize, //lines before the first line of lines
+	lines: Vec<u32> //line of the given glsl for every line after prefix
+}
+
+impl GlslPreprocessor
+{
+	#[inline]
+	pub fn new() -> Self
+	{
+		Self::default()
+	}
+
+	//makes glsl available as #include "name" from every file, takes precedence over include_file
+	pub fn include(&mut self, name: &str, glsl: &str) -> &mut Self
+	{
+		self.includes.insert(name.to_string(), glsl.to_string());
+		self
+	}
+
+	//makes glsl available to #include "name" with a name relative to the including file
+	pub fn include_file(&mut self, path: &str, glsl: &str) -> &mut Self
+	{
+		self.files.insert(path.to_string(), glsl.to_string());
+		self
+	}
+
+	//path of the given vertex and fragment glsl for include_file (empty by default)
+	pub fn source_path(&mut self, path: &str) -> &mut Self
+	{
+		self.source_path = path.to_string();
+		self
+	}
+
+	//names of the well-formed #include lines of glsl, for loading the files up front
+	pub fn include_names(glsl: &str) -> impl Iterator<Item = &str>
+	{
+		glsl.lines().filter_map(|text| text.trim_start().strip_prefix("#include")?.trim().strip_prefix('"')?.strip_suffix('"'))
+	}
+
+	//path of #include "name" in the file at path, "." and ".." are resolved
+	pub fn include_path(path: &str, name: &str) -> String
+	{
+		let mut components: Vec<&str> = path.split('/').collect();
+		components.pop();
+		for component in name.split('/')
+		{
+			match component
+			{
+				"" | "." => (),
+				".." => { components.pop(); },
+				component => components.push(component)
+			}
+		}
+		components.retain(|component| !component.is_empty());
+		components.join("/")
+	}
+
+	//#define name value
+	pub fn define(&mut self, name: &str, value: &str) -> &mut Self
+	{
+		self.defines.push((name.to_string(), value.to_string()));
+		self
+	}
+
+	fn process(&self, stage: ShaderStage, header: &str, glsl: &str) -> Result<GlslSource, ShaderError>
+	{
+		let mut body = String::new();
+		let mut lines = Vec::new();
+		self.expand(stage, glsl, None, &mut vec![self.source_path.clone()], &mut body, &mut lines)?;
+		//#extension has to come before any non-preprocessor token, so directly after #version
+		let (version, header) = match header.split_once('\n')
+		{
+			Some((version, rest)) if version.starts_with("#version") => (version, rest),
+			_ if header.starts_with("#version") => (header, ""),
+			_ => ("", header)
+		};
+		let mut prefix = String::new();
+		if !version.is_empty() { prefix.push_str(version); prefix.push('\n'); }
+		for extension in Self::extensions(stage, &body) { prefix.push_str(&format!("#extension {} : enable\n", extension)); }
+		if !header.is_empty() { prefix.push_str(header); prefix.push('\n'); }
+		for (name, value) in &self.defines { prefix.push_str(&format!("#define {} {}\n", name, value)); }
+		let prefix_lines = prefix.matches('\n').count();
+		prefix.push_str(&body);
+		Ok(GlslSource { glsl: prefix, prefix: prefix_lines, lines })
+	}
+
+	//line: the line of the #include in the given glsl, None for the given glsl itself
+	//stack: paths of the files being expanded, the last one is glsl
+	fn expand(&self, stage: ShaderStage, glsl: &str, line: Option<u32>, stack: &mut Vec<String>, body: &mut String, lines: &mut Vec<u32>) -> Result<(), ShaderError>
+	{
+		for (i, text) in glsl.lines().enumerate()
+		{
+			let line = line.unwrap_or(i as u32 + 1);
+			let Some(include) = text.trim_start().strip_prefix("#include") else
+			{
+				body.push_str(text);
+				body.push('\n');
+				lines.push(line);
+				continue;
+			};
+			let error = |log: String| ShaderError { stage, log, lines: vec![line] };
+			let name = include.trim().strip_prefix('"').and_then(|name| name.strip_suffix('"')).ok_or_else(|| error(format!("Malformed include {}.", include.trim())))?;
+			//named includes count as files at the path of their name
+			let (path, source) = match self.includes.get(name)
+			{
+				Some(source) => (name.to_string(), source),
+				None =>
+				{
+					let path = Self::include_path(stack.last().unwrap(), name);
+					let source = self.files.get(&path).ok_or_else(|| error(format!("Unknown include \"{}\".", name)))?;
+					(path, source)
+				}
+			};
+			if stack.contains(&path) { return Err(error(format!("Recursive include \"{}\".", name))); }
+			stack.push(path);
+			self.expand(stage, source, Some(line), stack, body, lines)?;
+			stack.pop();
+		}
+		Ok(())
+	}
+
+	#[cfg(target_arch = "wasm32")]
+	fn extensions(stage: ShaderStage, body: &str) -> Vec<&'static str>
+	{
+		if stage != ShaderStage::Fragment { return Vec::new(); }
+		FRAGMENT_EXTENSIONS.iter()
+			.filter(|(extension, identifiers)| !body.contains(extension) && identifiers.iter().any(|identifier| body.contains(identifier)))
+			.map(|(extension, _)| *extension)
+			.collect()
+	}
+
+	//desktop GLSL 1.10 has everything built in
+	#[cfg(not(target_arch = "wasm32"))]
+	fn extensions(_: ShaderStage, _: &str) -> Vec<&'static str>
+	{
+		Vec::new()
+	}
+}
+
+impl<T: VertexStreams, I: AttributesReprCpacked> ShaderVariants<T, I>
+{
+	//bitmask of a flag given to Gl::new_shader_variants
+	pub fn flag(&self, name: &str) -> u32
+	{
+		match self.flags.iter().position(|flag| flag == name)
+		{
+			Some(i) => 1 << i,
+			None => panic!("ShaderVariants::flag: Unknown flag \"{}\".", name)
+		}
+	}
+
+	#[inline]
+	pub fn get(&self, mask: u32) -> &Shader<T, I>
+	{
+		&self.shaders[mask as usize]
+	}
+
+	pub fn variant(&self, flags: &[&str]) -> &Shader<T, I>
+	{
+		self.get(flags.iter().map(|flag| self.flag(flag)).fold(0, |mask, flag| mask | flag))
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShaderStage
+{
+	Vertex,
+	Fragment,
+	Link
+}
+
+impl ShaderStage
+{
+	fn code(&self) -> u32
+	{
+		match self
+		{
+			Self::Vertex => glow::VERTEX_SHADER,
+			Self::Fragment => glow::FRAGMENT_SHADER,
+			Self::Link => unreachable!()
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderError
+{
+	pub stage: ShaderStage,
+	pub log: String, //raw info log of the driver
+	pub lines: Vec<u32> //lines of the given glsl (without header) the log refers to, errors in includes point to the #include
+}
+
+impl ShaderError
+{
+	fn compile(stage: ShaderStage, log: String, source: &GlslSource) -> Self
+	{
+		let lines = log.lines()
+			.filter_map(Self::log_line)
+			.filter_map(|line| source.lines.get((line as usize).checked_sub(source.prefix + 1)?).copied())
+			.collect();
+		Self { stage, log, lines }
+	}
+
+	fn link(log: String) -> Self
+	{
+		Self { stage: ShaderStage::Link, log, lines: Vec::new() }
+	}
+
+	//"ERROR: 0:12: ..." (ANGLE, WebGL), "0:12(5): error: ..." (Mesa), "0(12) : error ..." (nVidia)
+	fn log_line(line: &str) -> Option<u32>
+	{
+		let bytes = line.as_bytes();
+		let mut i = 0;
+		while i < bytes.len()
+		{
+			let start = i;
+			while i < bytes.len() && bytes[i].is_ascii_digit() { i += 1; }
+			if i > start && i + 1 < bytes.len() && (bytes[i] == b':' || bytes[i] == b'(') && bytes[i + 1].is_ascii_digit()
+			{
+				let begin = i + 1;
+				let mut end = begin;
+				while end < bytes.len() && bytes[end].is_ascii_digit() { end += 1; }
+				return line[begin..end].parse().ok();
+			}
+			i = i.max(start + 1);
+		}
+		None
+	}
+}
+
+impl std::fmt::Display for ShaderError
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+	{
+		match self.stage
+		{
+			ShaderStage::Vertex => write!(f, "Vertex shader compilation failed")?,
+			ShaderStage::Fragment => write!(f, "Fragment shader compilation failed")?,
+			ShaderStage::Link => write!(f, "Shader linking failed")?
+		}
+		if !self.lines.is_empty()
+		{
+			let lines: Vec<String> = self.lines.iter().map(|line| line.to_string()).collect();
+			write!(f, " (line {})", lines.join(", "))?;
+		}
+		write!(f, ":\n{}", self.log)
+	}
+}
+
+impl std::error::Error for ShaderError {}
+
+impl<U: UniformType + ?Sized> UniformKey<U>
+{
+	//number of array elements declared in the shader (1 for non-arrays)
+	#[inline]
+	pub fn size(&self) -> u32
+	{
+		self.size
+	}
+
+	fn check_len(&self, len: usize)
+	{
+		if len > self.size as usize { panic!("The uniform array has {} elements but the shader only declares {}.", len, self.size); }
+	}
+}
+
+pub unsafe trait UniformType
 {
 	const CODE: u32;
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>);
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>);
 }
 
 unsafe impl UniformType for f32
 {
 	const CODE: u32 = glow::FLOAT;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_1_f32(Some(&key.key), *self); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_1_f32(Some(&key.key), *self); } }
 }
 
 unsafe impl UniformType for Vec2
 {
 	const CODE: u32 = glow::FLOAT_VEC2;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_2_f32(Some(&key.key), self.0, self.1); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_2_f32(Some(&key.key), self.0, self.1); } }
 }
 
 unsafe impl UniformType for Vec3
 {
 	const CODE: u32 = glow::FLOAT_VEC3;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_3_f32(Some(&key.key), self.0, self.1, self.2); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_3_f32(Some(&key.key), self.0, self.1, self.2); } }
 }
 
 unsafe impl UniformType for Vec4
 {
 	const CODE: u32 = glow::FLOAT_VEC4;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_4_f32(Some(&key.key), self.0, self.1, self.2, self.3); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_4_f32(Some(&key.key), self.0, self.1, self.2, self.3); } }
 }
 
 unsafe impl UniformType for i32
 {
 	const CODE: u32 = glow::INT;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_1_i32(Some(&key.key), *self); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_1_i32(Some(&key.key), *self); } }
 }
 
 unsafe impl UniformType for (i32, i32)
 {
 	const CODE: u32 = glow::INT_VEC2;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_2_i32(Some(&key.key), self.0, self.1); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_2_i32(Some(&key.key), self.0, self.1); } }
 }
 
 unsafe impl UniformType for (i32, i32, i32)
 {
 	const CODE: u32 = glow::INT_VEC3;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_3_i32(Some(&key.key), self.0, self.1, self.2); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_3_i32(Some(&key.key), self.0, self.1, self.2); } }
 }
 
 unsafe impl UniformType for (i32, i32, i32, i32)
 {
 	const CODE: u32 = glow::INT_VEC4;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_4_i32(Some(&key.key), self.0, self.1, self.2, self.3); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_4_i32(Some(&key.key), self.0, self.1, self.2, self.3); } }
 }
 
 unsafe impl UniformType for u32
 {
 	const CODE: u32 = glow::UNSIGNED_INT;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_1_u32(Some(&key.key), *self); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_1_u32(Some(&key.key), *self); } }
 }
 
 unsafe impl UniformType for (u32, u32)
 {
 	const CODE: u32 = glow::UNSIGNED_INT_VEC2;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_2_u32(Some(&key.key), self.0, self.1); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_2_u32(Some(&key.key), self.0, self.1); } }
 }
 
 unsafe impl UniformType for (u32, u32, u32)
 {
 	const CODE: u32 = glow::UNSIGNED_INT_VEC3;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_3_u32(Some(&key.key), self.0, self.1, self.2); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_3_u32(Some(&key.key), self.0, self.1, self.2); } }
 }
 
 unsafe impl UniformType for (u32, u32, u32, u32)
 {
 	const CODE: u32 = glow::UNSIGNED_INT_VEC4;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_4_u32(Some(&key.key), self.0, self.1, self.2, self.3); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_4_u32(Some(&key.key), self.0, self.1, self.2, self.3); } }
 }
 
 unsafe impl UniformType for Mat2
 {
 	const CODE: u32 = glow::FLOAT_MAT2;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_matrix_2_f32_slice(Some(&key.key), false, &self.to_array()); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_matrix_2_f32_slice(Some(&key.key), false, &self.to_array()); } }
 }
 
 unsafe impl UniformType for Mat3
 {
 	const CODE: u32 = glow::FLOAT_MAT3;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_matrix_3_f32_slice(Some(&key.key), false, &self.to_array()); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_matrix_3_f32_slice(Some(&key.key), false, &self.to_array()); } }
 }
 
 unsafe impl UniformType for Mat4
 {
 	const CODE: u32 = glow::FLOAT_MAT4;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.uniform_matrix_4_f32_slice(Some(&key.key), false, &self.to_array()); } }
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { unsafe { pipeline.gl.raw.context().uniform_matrix_4_f32_slice(Some(&key.key), false, &self.to_array()); } }
+}
+
+unsafe impl UniformType for [f32]
+{
+	const CODE: u32 = glow::FLOAT;
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>)
+	{
+		key.check_len(self.len());
+		unsafe { pipeline.gl.raw.context().uniform_1_f32_slice(Some(&key.key), self); }
+	}
+}
+
+unsafe impl UniformType for [Vec2]
+{
+	const CODE: u32 = glow::FLOAT_VEC2;
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>)
+	{
+		key.check_len(self.len());
+		let data: Vec<f32> = self.iter().flat_map(|v| [v.0, v.1]).collect();
+		unsafe { pipeline.gl.raw.context().uniform_2_f32_slice(Some(&key.key), &data); }
+	}
+}
+
+unsafe impl UniformType for [Vec3]
+{
+	const CODE: u32 = glow::FLOAT_VEC3;
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>)
+	{
+		key.check_len(self.len());
+		let data: Vec<f32> = self.iter().flat_map(|v| [v.0, v.1, v.2]).collect();
+		unsafe { pipeline.gl.raw.context().uniform_3_f32_slice(Some(&key.key), &data); }
+	}
+}
+
+unsafe impl UniformType for [Vec4]
+{
+	const CODE: u32 = glow::FLOAT_VEC4;
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>)
+	{
+		key.check_len(self.len());
+		let data: Vec<f32> = self.iter().flat_map(|v| [v.0, v.1, v.2, v.3]).collect();
+		unsafe { pipeline.gl.raw.context().uniform_4_f32_slice(Some(&key.key), &data); }
+	}
+}
+
+unsafe impl UniformType for [Mat4]
+{
+	const CODE: u32 = glow::FLOAT_MAT4;
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>)
+	{
+		key.check_len(self.len());
+		let data: Vec<f32> = self.iter().flat_map(|v| v.to_array()).collect();
+		unsafe { pipeline.gl.raw.context().uniform_matrix_4_f32_slice(Some(&key.key), false, &data); }
+	}
+}
+
+unsafe impl UniformType for Texture
+{
+	const CODE: u32 = glow::SAMPLER_2D;
+	#[inline]
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { pipeline.bind_texture(key.unit, glow::TEXTURE_2D, self.texture); }
 }
 
-unsafe impl<const P: bool> UniformType for Texture<P>
+unsafe impl UniformType for [&Texture]
 {
 	const CODE: u32 = glow::SAMPLER_2D;
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>)
+	{
+		key.check_len(self.len());
+		for (i, texture) in self.iter().enumerate() { pipeline.bind_texture(key.unit + i as u32, glow::TEXTURE_2D, texture.texture); }
+	}
+}
+
+unsafe impl UniformType for CubeTexture
+{
+	const CODE: u32 = glow::SAMPLER_CUBE;
 	#[inline]
-	unsafe fn set<T: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T>, key: &UniformKey<Self>)
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>) { pipeline.bind_texture(key.unit, glow::TEXTURE_CUBE_MAP, self.texture); }
+}
+
+unsafe impl UniformType for [&CubeTexture]
+{
+	const CODE: u32 = glow::SAMPLER_CUBE;
+	unsafe fn set<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, key: &UniformKey<Self>)
 	{
-		if let Some(id) = (0..8).map(|id| (id + pipeline.texture_active) % 8).filter(|id| pipeline.texture_lock & (1 << id) == 0).next()
-		{
-            unsafe
-            {
-                pipeline.gl.raw.uniform_1_i32(Some(&key.key), id as i32);
-                pipeline.gl.raw.active_texture(glow::TEXTURE0 + id as u32);
-                pipeline.gl.raw.bind_texture(glow::TEXTURE_2D, Some(self.texture));
-            }
-			pipeline.texture_active = (id + 1) % 8;
-			if P { pipeline.texture_lock |= 1 << id; }
-		}
-		pipeline.texture_used = true;
+		key.check_len(self.len());
+		for (i, texture) in self.iter().enumerate() { pipeline.bind_texture(key.unit + i as u32, glow::TEXTURE_CUBE_MAP, texture.texture); }
+	}
+}
+
+//set with Pipeline::uniform_struct, see #[derive(UniformStruct)]
+pub trait UniformStruct
+{
+	fn set_uniforms<T: VertexStreams, I: AttributesReprCpacked>(&self, pipeline: &mut Pipeline<T, I>, name: &str);
+}
+
+pub use gru_opengl_derive::UniformStruct;
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn log_line()
+	{
+		assert_eq!(ShaderError::log_line("0(12) : error C0000: syntax error, unexpected identifier"), Some(12)); //nVidia
+		assert_eq!(ShaderError::log_line("0:12(5): error: `x' undeclared"), Some(12)); //Mesa
+		assert_eq!(ShaderError::log_line("ERROR: 0:12: 'x' : undeclared identifier"), Some(12)); //ANGLE
+		assert_eq!(ShaderError::log_line("ERROR: 2 compilation errors.  No code generated."), None);
+	}
+
+	#[test]
+	fn preprocessor_lines()
+	{
+		let mut preprocessor = GlslPreprocessor::new();
+		preprocessor
+			.source_path("water/ocean.vert")
+			.include_file("water/waves.glsl", "float a;\n#include \"../common.glsl\"\nfloat b;")
+			.include_file("common.glsl", "float c;\nfloat d;")
+			.define("A", "1")
+			.define("B", "2");
+		let source = preprocessor.process(ShaderStage::Vertex, "#version 110", "void f();\n#include \"waves.glsl\"\nvoid main() {}").unwrap();
+		assert_eq!(source.prefix, 3); //#version and the 2 #defines
+		assert_eq!(source.lines, [1, 2, 2, 2, 2, 3]); //the nested include maps to the outermost #include
+		assert_eq!(source.glsl.lines().nth(8), Some("void main() {}"));
+		let error = ShaderError::compile(ShaderStage::Vertex, "ERROR: 0:6: 'c' : redefinition\nERROR: 0:9: 'main' : syntax error".to_string(), &source);
+		assert_eq!(error.lines, [2, 3]);
+	}
+
+	#[test]
+	fn preprocessor_errors()
+	{
+		let mut preprocessor = GlslPreprocessor::new();
+		preprocessor.include_file("a.glsl", "#include \"b.glsl\"").include_file("b.glsl", "\n#include \"a.glsl\"");
+		let error = preprocessor.process(ShaderStage::Fragment, "", "void f();\n#include \"a.glsl\"").err().unwrap();
+		assert!(error.log.starts_with("Recursive include") && error.lines == [2]);
+		let error = preprocessor.process(ShaderStage::Fragment, "", "\n\n#include \"c.glsl\"").err().unwrap();
+		assert!(error.log.starts_with("Unknown include") && error.lines == [3]);
 	}
 }
@@ -0,0 +1,272 @@
+use super::*;
+use std::cell::Cell;
+
+type Program = <Context as HasContext>::Program;
+type Buffer = <Context as HasContext>::Buffer;
+type VertexArray = <Context as HasContext>::VertexArray;
+type Framebuffer = <Context as HasContext>::Framebuffer;
+type Texture = <Context as HasContext>::Texture;
+
+//the glow context with a cache of the bound objects, shared by Gl and all objects
+//there is no Deref to Context, so nothing can bind past the cache (clippy.toml also catches it on a bare Context)
+pub(crate) struct Raw
+{
+	gl: Context,
+	scratch_unit: u32, //the last texture unit, never assigned to samplers
+	max_anisotropy: Option<f32>, //see Capabilities
+	state: RefCell<State>,
+	vertex_arrays: RefCell<AHashMap<VaoKey, VertexArray>>, //recorded by Pipeline::draw
+	stats: Cell<StateStats>
+}
+
+struct State
+{
+	program: Option<Program>,
+	array_buffer: Option<Buffer>,
+	element_buffer: Option<Option<Buffer>>, //part of the vertex array state, None if unknown
+	vertex_array: Option<VertexArray>,
+	framebuffer: Option<Framebuffer>,
+	active_unit: usize,
+	textures: Vec<[Option<Texture>; 2]> //(TEXTURE_2D, TEXTURE_CUBE_MAP) per unit
+}
+
+//see Gl::state_stats
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StateStats
+{
+	pub issued: u64,
+	pub skipped: u64
+}
+
+impl Gl
+{
+	//binds and state changes since creation or the last reset
+	#[inline]
+	pub fn state_stats(&self) -> StateStats
+	{
+		self.raw.stats()
+	}
+
+	#[inline]
+	pub fn reset_state_stats(&mut self)
+	{
+		self.raw.reset_stats();
+	}
+}
+
+macro_rules! cached
+{
+	($self: ident, $cache: expr, $value: expr, $set: expr) =>
+	{
+		if $cache == $value { $self.count(false); }
+		else
+		{
+			$set;
+			$cache = $value;
+			$self.count(true);
+		}
+	}
+}
+
+#[allow(clippy::disallowed_methods)]
+impl Raw
+{
+	pub(crate) fn new(gl: Context, capabilities: &Capabilities) -> Self
+	{
+		let state = State
+		{
+			program: None,
+			array_buffer: None,
+			element_buffer: Some(None),
+			vertex_array: None,
+			framebuffer: None,
+			active_unit: 0,
+			textures: Vec::new()
+		};
+		Self { gl, scratch_unit: capabilities.max_texture_units - 1, max_anisotropy: capabilities.max_anisotropy, state: RefCell::new(state), vertex_arrays: RefCell::new(AHashMap::new()), stats: Cell::new(StateStats::default()) }
+	}
+
+	//uncached calls, binds and deletes go through the methods below
+	#[inline]
+	pub(crate) fn context(&self) -> &Context
+	{
+		&self.gl
+	}
+
+	#[inline]
+	pub(crate) fn max_anisotropy(&self) -> Option<f32>
+	{
+		self.max_anisotropy
+	}
+
+	#[inline]
+	pub(crate) fn count(&self, issued: bool)
+	{
+		let mut stats = self.stats.get();
+		if issued { stats.issued += 1; } else { stats.skipped += 1; }
+		self.stats.set(stats);
+	}
+
+	#[inline]
+	pub(crate) fn stats(&self) -> StateStats
+	{
+		self.stats.get()
+	}
+
+	#[inline]
+	pub(crate) fn reset_stats(&self)
+	{
+		self.stats.set(StateStats::default());
+	}
+
+	pub(crate) unsafe fn use_program(&self, program: Option<Program>)
+	{
+		let state = &mut *self.state.borrow_mut();
+		cached!(self, state.program, program, unsafe { self.gl.use_program(program) });
+	}
+
+	pub(crate) unsafe fn bind_buffer(&self, target: u32, buffer: Option<Buffer>)
+	{
+		let state = &mut *self.state.borrow_mut();
+		match target
+		{
+			glow::ARRAY_BUFFER => cached!(self, state.array_buffer, buffer, unsafe { self.gl.bind_buffer(target, buffer) }),
+			glow::ELEMENT_ARRAY_BUFFER => cached!(self, state.element_buffer, Some(buffer), unsafe { self.gl.bind_buffer(target, buffer) }),
+			_ =>
+			{
+				unsafe { self.gl.bind_buffer(target, buffer); }
+				self.count(true);
+			}
+		}
+	}
+
+	//with None also makes sure that element buffer binds do not end up in a vertex array (no-op without vertex array support)
+	pub(crate) unsafe fn bind_vertex_array(&self, vertex_array: Option<VertexArray>)
+	{
+		let state = &mut *self.state.borrow_mut();
+		if state.vertex_array != vertex_array { state.element_buffer = None; }
+		cached!(self, state.vertex_array, vertex_array, unsafe { self.gl.bind_vertex_array(vertex_array) });
+	}
+
+	#[inline]
+	pub(crate) fn framebuffer(&self) -> Option<Framebuffer>
+	{
+		self.state.borrow().framebuffer
+	}
+
+	pub(crate) unsafe fn bind_framebuffer(&self, target: u32, framebuffer: Option<Framebuffer>)
+	{
+		let state = &mut *self.state.borrow_mut();
+		match target
+		{
+			glow::FRAMEBUFFER => cached!(self, state.framebuffer, framebuffer, unsafe { self.gl.bind_framebuffer(target, framebuffer) }),
+			_ =>
+			{
+				//READ_FRAMEBUFFER or DRAW_FRAMEBUFFER leave the FRAMEBUFFER binding ambiguous
+				unsafe { self.gl.bind_framebuffer(target, framebuffer); }
+				state.framebuffer = None;
+				self.count(true);
+			}
+		}
+	}
+
+	pub(crate) unsafe fn active_texture(&self, unit: u32)
+	{
+		let state = &mut *self.state.borrow_mut();
+		cached!(self, state.active_unit, (unit - glow::TEXTURE0) as usize, unsafe { self.gl.active_texture(unit) });
+	}
+
+	pub(crate) unsafe fn bind_texture(&self, target: u32, texture: Option<Texture>)
+	{
+		let state = &mut *self.state.borrow_mut();
+		let index = match target
+		{
+			glow::TEXTURE_2D => 0,
+			glow::TEXTURE_CUBE_MAP => 1,
+			_ =>
+			{
+				unsafe { self.gl.bind_texture(target, texture); }
+				self.count(true);
+				return;
+			}
+		};
+		let unit = state.active_unit;
+		if state.textures.len() <= unit { state.textures.resize(unit + 1, [None; 2]); }
+		cached!(self, state.textures[unit][index], texture, unsafe { self.gl.bind_texture(target, texture) });
+	}
+
+	//for creating and updating textures, keeps the samplers bound by a Pipeline intact
+	pub(crate) unsafe fn edit_texture(&self, target: u32, texture: Option<Texture>)
+	{
+		unsafe
+		{
+			self.active_texture(glow::TEXTURE0 + self.scratch_unit);
+			self.bind_texture(target, texture);
+		}
+	}
+
+	#[inline]
+	pub(crate) fn vertex_array(&self, key: &VaoKey) -> Option<VertexArray>
+	{
+		self.vertex_arrays.borrow().get(key).copied()
+	}
+
+	#[inline]
+	pub(crate) fn insert_vertex_array(&self, key: VaoKey, vertex_array: VertexArray)
+	{
+		self.vertex_arrays.borrow_mut().insert(key, vertex_array);
+	}
+
+	//for dropped shaders and buffers
+	pub(crate) unsafe fn delete_vertex_arrays(&self, stale: impl Fn(&VaoKey) -> bool)
+	{
+		self.vertex_arrays.borrow_mut().retain(|key, vertex_array|
+		{
+			let stale = stale(key);
+			if stale { unsafe { self.delete_vertex_array(*vertex_array); } }
+			!stale
+		});
+	}
+
+	//deleted objects are unbound by OpenGL and their names can be reused
+
+	pub(crate) unsafe fn delete_program(&self, program: Program)
+	{
+		let state = &mut *self.state.borrow_mut();
+		if state.program == Some(program) { state.program = None; }
+		unsafe { self.gl.delete_program(program); }
+	}
+
+	pub(crate) unsafe fn delete_buffer(&self, buffer: Buffer)
+	{
+		let state = &mut *self.state.borrow_mut();
+		if state.array_buffer == Some(buffer) { state.array_buffer = None; }
+		if state.element_buffer == Some(Some(buffer)) { state.element_buffer = Some(None); }
+		unsafe { self.gl.delete_buffer(buffer); }
+	}
+
+	pub(crate) unsafe fn delete_vertex_array(&self, vertex_array: VertexArray)
+	{
+		let state = &mut *self.state.borrow_mut();
+		if state.vertex_array == Some(vertex_array)
+		{
+			state.vertex_array = None;
+			state.element_buffer = None;
+		}
+		unsafe { self.gl.delete_vertex_array(vertex_array); }
+	}
+
+	pub(crate) unsafe fn delete_framebuffer(&self, framebuffer: Framebuffer)
+	{
+		let state = &mut *self.state.borrow_mut();
+		if state.framebuffer == Some(framebuffer) { state.framebuffer = None; }
+		unsafe { self.gl.delete_framebuffer(framebuffer); }
+	}
+
+	pub(crate) unsafe fn delete_texture(&self, texture: Texture)
+	{
+		let state = &mut *self.state.borrow_mut();
+		for unit in state.textures.iter_mut() { for bound in unit.iter_mut() { if *bound == Some(texture) { *bound = None; } } }
+		unsafe { self.gl.delete_texture(texture); }
+	}
+}
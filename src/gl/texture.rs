@@ -2,27 +2,78 @@ use super::*;
 
 impl Gl
 {
-	pub fn new_texture<const P: bool>(&mut self, TextureConfig { size, channel, mipmap, wrap_s, wrap_t }: &TextureConfig, data: &[u8]) -> Texture<P>
+	pub fn new_texture(&mut self, config: &TextureConfig, data: &[u8]) -> Texture
 	{
-		if size & (size - 1) != 0 { panic!("Gl::new_texture: Size is not a power of 2."); }
-		if size.pow(2) * channel.bytes() != data.len() as u32 { panic!("Gl::new_texture: Data has the wrong length."); }
+		let TextureConfig { width, height, channel, .. } = *config;
+		if channel.depth() { panic!("Gl::new_texture: Depth textures are only available as framebuffer attachments."); }
+		if let Err(err) = self.capabilities.check_texture(channel) { panic!("Gl::new_texture: {}", err); }
+		if let Err(err) = channel.check_data(data) { panic!("Gl::new_texture: {}", err); }
+		if width * height * channel.bytes() != data.len() as u32 { panic!("Gl::new_texture: Data has the wrong length."); }
+		self.raw_texture(config, Some(data))
+	}
+
+	pub(crate) fn raw_texture(&mut self, config: &TextureConfig, data: Option<&[u8]>) -> Texture
+	{
+		let TextureConfig { width, height, channel, mipmap, filter, wrap_s, wrap_t } = Self::npot_fallback(config);
+		let gl = &self.raw;
+		unsafe
+		{
+			let texture = gl.context().create_texture().unwrap();
+			gl.edit_texture(glow::TEXTURE_2D, Some(texture));
+			gl.context().tex_image_2d(glow::TEXTURE_2D, 0, channel.internal_format() as i32, width as i32, height as i32, 0, channel.format(), channel.data_type(), data);
+			gl.context().tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap_s.wrap() as i32);
+			gl.context().tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap_t.wrap() as i32);
+			if mipmap { gl.context().generate_mipmap(glow::TEXTURE_2D); }
+			filter.apply(gl, glow::TEXTURE_2D, mipmap, channel);
+			Texture { gl: gl.clone(), texture, width, height, channel, mipmap }
+		}
+	}
+
+	//faces in the order +X, -X, +Y, -Y, +Z, -Z
+	pub fn new_cube_texture(&mut self, config: &CubeTextureConfig, faces: [&[u8]; 6]) -> CubeTexture
+	{
+		let CubeTextureConfig { size, channel, mut mipmap, filter } = *config;
+		if channel.depth() { panic!("Gl::new_cube_texture: Depth textures are only available as framebuffer attachments."); }
+		if let Err(err) = self.capabilities.check_texture(channel) { panic!("Gl::new_cube_texture: {}", err); }
+		if let Some(err) = faces.iter().find_map(|face| channel.check_data(face).err()) { panic!("Gl::new_cube_texture: {}", err); }
+		if faces.iter().any(|face| size * size * channel.bytes() != face.len() as u32) { panic!("Gl::new_cube_texture: Data has the wrong length."); }
+		if cfg!(target_arch = "wasm32") && mipmap && !size.is_power_of_two()
+		{
+			log(&format!("Gl::new_cube_texture: Falling back to no mipmaps for the non-power-of-2 size {}.", size));
+			mipmap = false;
+		}
 		let gl = &self.raw;
 		unsafe
 		{
-			let texture = gl.create_texture().unwrap();
-			gl.bind_texture(glow::TEXTURE_2D, Some(texture));
-			gl.tex_image_2d(glow::TEXTURE_2D, 0, channel.format() as i32, *size as i32, *size as i32, 0, channel.format(), glow::UNSIGNED_BYTE, Some(data));
-			gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap_s.wrap() as i32);
-			gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap_t.wrap() as i32);
-			if *mipmap
+			let texture = gl.context().create_texture().unwrap();
+			gl.edit_texture(glow::TEXTURE_CUBE_MAP, Some(texture));
+			for (i, face) in faces.iter().enumerate()
+			{
+				gl.context().tex_image_2d(glow::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32, 0, channel.internal_format() as i32, size as i32, size as i32, 0, channel.format(), channel.data_type(), Some(face));
+			}
+			gl.context().tex_parameter_i32(glow::TEXTURE_CUBE_MAP, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+			gl.context().tex_parameter_i32(glow::TEXTURE_CUBE_MAP, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+			if mipmap { gl.context().generate_mipmap(glow::TEXTURE_CUBE_MAP); }
+			filter.apply(gl, glow::TEXTURE_CUBE_MAP, mipmap, channel);
+			CubeTexture { gl: gl.clone(), texture, size, channel, mipmap }
+		}
+	}
+
+	//WebGL 1 only supports non-power-of-2 textures with clamped wrapping and without mipmaps
+	fn npot_fallback(config: &TextureConfig) -> TextureConfig
+	{
+		let mut config = config.clone();
+		if cfg!(target_arch = "wasm32") && !(config.width.is_power_of_two() && config.height.is_power_of_two())
+		{
+			if config.mipmap || matches!(config.wrap_s, TextureWrap::Repeat) || matches!(config.wrap_t, TextureWrap::Repeat)
 			{
-				gl.generate_mipmap(glow::TEXTURE_2D);
-				gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR_MIPMAP_LINEAR as i32);
-			} else { gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32); }
-			gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
-			gl.bind_texture(glow::TEXTURE_2D, None);
-			Texture { gl: gl.clone(), texture, size: *size }
+				log(&format!("Gl::new_texture: Falling back to clamped wrapping without mipmaps for the non-power-of-2 size ({}/{}).", config.width, config.height));
+			}
+			config.mipmap = false;
+			config.wrap_s = TextureWrap::Clamp;
+			config.wrap_t = TextureWrap::Clamp;
 		}
+		config
 	}
 }
 
@@ -31,9 +82,21 @@ pub enum TextureChannel
 {
 	A,
 	RGB,
-	RGBA
+	RGBA,
+	RGBFloat, //32 bit floats, see Capabilities::check_texture
+	RGBAFloat,
+	RGBHalf, //16 bit floats, data can only be uploaded on desktop
+	RGBAHalf,
+	Depth, //framebuffer attachments only
+	DepthStencil //framebuffer attachments only
 }
 
+//HALF_FLOAT_OES of OES_texture_half_float differs from the OpenGL 3.0 HALF_FLOAT
+#[cfg(target_arch = "wasm32")]
+const HALF_FLOAT: u32 = 0x8D61;
+#[cfg(not(target_arch = "wasm32"))]
+const HALF_FLOAT: u32 = glow::HALF_FLOAT;
+
 impl TextureChannel
 {
 	fn bytes(&self) -> u32
@@ -42,7 +105,13 @@ impl TextureChannel
 		{
 			Self::A => 1,
 			Self::RGB => 3,
-			Self::RGBA => 4
+			Self::RGBA => 4,
+			Self::RGBFloat => 12,
+			Self::RGBAFloat => 16,
+			Self::RGBHalf => 6,
+			Self::RGBAHalf => 8,
+			Self::Depth => 4,
+			Self::DepthStencil => 4
 		}
 	}
 
@@ -51,10 +120,72 @@ impl TextureChannel
 		match self
 		{
 			Self::A => glow::ALPHA,
-			Self::RGB => glow::RGB,
-			Self::RGBA => glow::RGBA
+			Self::RGB | Self::RGBFloat | Self::RGBHalf => glow::RGB,
+			Self::RGBA | Self::RGBAFloat | Self::RGBAHalf => glow::RGBA,
+			Self::Depth => glow::DEPTH_COMPONENT,
+			Self::DepthStencil => glow::DEPTH_STENCIL
+		}
+	}
+
+	//WebGL 1 derives the storage from format and type, desktop needs sized float formats
+	fn internal_format(&self) -> u32
+	{
+		match self
+		{
+			#[cfg(not(target_arch = "wasm32"))]
+			Self::RGBFloat => glow::RGB32F,
+			#[cfg(not(target_arch = "wasm32"))]
+			Self::RGBAFloat => glow::RGBA32F,
+			#[cfg(not(target_arch = "wasm32"))]
+			Self::RGBHalf => glow::RGB16F,
+			#[cfg(not(target_arch = "wasm32"))]
+			Self::RGBAHalf => glow::RGBA16F,
+			_ => self.format()
+		}
+	}
+
+	fn data_type(&self) -> u32
+	{
+		match self
+		{
+			Self::A | Self::RGB | Self::RGBA => glow::UNSIGNED_BYTE,
+			Self::RGBFloat | Self::RGBAFloat => glow::FLOAT,
+			Self::RGBHalf | Self::RGBAHalf => HALF_FLOAT,
+			Self::Depth => glow::UNSIGNED_INT,
+			Self::DepthStencil => glow::UNSIGNED_INT_24_8
 		}
 	}
+
+	pub(crate) fn depth(&self) -> bool
+	{
+		matches!(self, Self::Depth | Self::DepthStencil)
+	}
+
+	pub(crate) fn float(&self) -> bool
+	{
+		matches!(self, Self::RGBFloat | Self::RGBAFloat)
+	}
+
+	pub(crate) fn half(&self) -> bool
+	{
+		matches!(self, Self::RGBHalf | Self::RGBAHalf)
+	}
+
+	//the data is viewed as f32 or u16 slice on the web
+	fn check_data(&self, data: &[u8]) -> Result<(), String>
+	{
+		if cfg!(target_arch = "wasm32") && self.half() { return Err("Half float data can not be uploaded on WebGL 1, only Framebuffer attachments are supported.".to_string()); }
+		let alignment = if self.float() { 4 } else if self.half() { 2 } else { 1 };
+		if !(data.as_ptr() as usize).is_multiple_of(alignment) { return Err(format!("The data of float textures has to be aligned to {} bytes.", alignment)); }
+		Ok(())
+	}
+
+	//WebGL 1 needs OES_texture_float_linear or OES_texture_half_float_linear to filter float textures linearly
+	fn linear_filtering(&self, gl: &Context) -> bool
+	{
+		let extension = if self.float() { "OES_texture_float_linear" } else if self.half() { "OES_texture_half_float_linear" } else { return true; };
+		!cfg!(target_arch = "wasm32") || gl.supported_extensions().contains(extension)
+	}
 }
 
 #[derive(Clone, Copy)]
@@ -76,20 +207,153 @@ impl TextureWrap
 	}
 }
 
+#[derive(Clone, Copy)]
+pub enum TextureFilterMode
+{
+	Nearest,
+	Linear
+}
+
+#[derive(Clone, Copy)]
+pub struct TextureFilter
+{
+	pub min: TextureFilterMode,
+	pub mag: TextureFilterMode,
+	pub mip: TextureFilterMode, //only used by textures with mipmaps
+	pub anisotropy: f32 //clamped to the supported maximum, ignored without EXT_texture_filter_anisotropic
+}
+
+impl TextureFilter
+{
+	pub const LINEAR: Self = Self { min: TextureFilterMode::Linear, mag: TextureFilterMode::Linear, mip: TextureFilterMode::Linear, anisotropy: 1.0 };
+	pub const NEAREST: Self = Self { min: TextureFilterMode::Nearest, mag: TextureFilterMode::Nearest, mip: TextureFilterMode::Nearest, anisotropy: 1.0 };
+
+	fn min(&self, mipmap: bool) -> u32
+	{
+		match (mipmap, self.min, self.mip)
+		{
+			(false, TextureFilterMode::Nearest, _) => glow::NEAREST,
+			(false, TextureFilterMode::Linear, _) => glow::LINEAR,
+			(true, TextureFilterMode::Nearest, TextureFilterMode::Nearest) => glow::NEAREST_MIPMAP_NEAREST,
+			(true, TextureFilterMode::Nearest, TextureFilterMode::Linear) => glow::NEAREST_MIPMAP_LINEAR,
+			(true, TextureFilterMode::Linear, TextureFilterMode::Nearest) => glow::LINEAR_MIPMAP_NEAREST,
+			(true, TextureFilterMode::Linear, TextureFilterMode::Linear) => glow::LINEAR_MIPMAP_LINEAR
+		}
+	}
+
+	fn mag(&self) -> u32
+	{
+		match self.mag
+		{
+			TextureFilterMode::Nearest => glow::NEAREST,
+			TextureFilterMode::Linear => glow::LINEAR
+		}
+	}
+
+	//expects the texture to be bound to target
+	pub(crate) fn apply(&self, gl: &Raw, target: u32, mipmap: bool, channel: TextureChannel)
+	{
+		let filter = if channel.linear_filtering(gl.context()) { *self } else
+		{
+			if [self.min, self.mag, self.mip].iter().any(|mode| matches!(mode, TextureFilterMode::Linear)) { log("TextureFilter: Falling back to nearest filtering, linear filtering of float textures is not supported."); }
+			Self { anisotropy: self.anisotropy, ..Self::NEAREST }
+		};
+		unsafe
+		{
+			gl.context().tex_parameter_i32(target, glow::TEXTURE_MIN_FILTER, filter.min(mipmap) as i32);
+			gl.context().tex_parameter_i32(target, glow::TEXTURE_MAG_FILTER, filter.mag() as i32);
+			if let Some(max) = gl.max_anisotropy() { gl.context().tex_parameter_f32(target, glow::TEXTURE_MAX_ANISOTROPY_EXT, self.anisotropy.clamp(1.0, max)); }
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct TextureConfig
 {
-	pub size: u32,
+	pub width: u32,
+	pub height: u32,
 	pub channel: TextureChannel,
 	pub mipmap: bool,
+	pub filter: TextureFilter,
 	pub wrap_s: TextureWrap,
     pub wrap_t: TextureWrap
 }
 
-impl<const P: bool> Texture<P>
+impl Texture
 {
+	#[inline]
+	pub fn width(&self) -> u32
+	{
+		self.width
+	}
+
+	#[inline]
+	pub fn height(&self) -> u32
+	{
+		self.height
+	}
+
+	#[inline]
+	pub fn size(&self) -> (u32, u32)
+	{
+		(self.width, self.height)
+	}
+
+	pub fn update_region(&mut self, x: u32, y: u32, width: u32, height: u32, data: &[u8])
+	{
+		if self.channel.depth() { panic!("Texture::update_region: Depth textures can not be updated."); }
+		if x + width > self.width || y + height > self.height { panic!("Texture::update_region: Region exceeds the texture size."); }
+		if let Err(err) = self.channel.check_data(data) { panic!("Texture::update_region: {}", err); }
+		if width * height * self.channel.bytes() != data.len() as u32 { panic!("Texture::update_region: Data has the wrong length."); }
+		let gl = &self.gl;
+		unsafe
+		{
+			gl.edit_texture(glow::TEXTURE_2D, Some(self.texture));
+			gl.context().tex_sub_image_2d(glow::TEXTURE_2D, 0, x as i32, y as i32, width as i32, height as i32, self.channel.format(), self.channel.data_type(), glow::PixelUnpackData::Slice(data));
+		}
+	}
+
+	pub fn set_filter(&mut self, filter: TextureFilter)
+	{
+		let gl = &self.gl;
+		unsafe { gl.edit_texture(glow::TEXTURE_2D, Some(self.texture)); }
+		filter.apply(gl, glow::TEXTURE_2D, self.mipmap, self.channel);
+	}
+
+	//only has an effect on textures created with mipmaps
+	pub fn regenerate_mipmap(&mut self)
+	{
+		if !self.mipmap { return; }
+		let gl = &self.gl;
+		unsafe
+		{
+			gl.edit_texture(glow::TEXTURE_2D, Some(self.texture));
+			gl.context().generate_mipmap(glow::TEXTURE_2D);
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct CubeTextureConfig
+{
+	pub size: u32,
+	pub channel: TextureChannel,
+	pub mipmap: bool,
+	pub filter: TextureFilter
+}
+
+impl CubeTexture
+{
+	#[inline]
 	pub fn size(&self) -> u32
 	{
 		self.size
 	}
+
+	pub fn set_filter(&mut self, filter: TextureFilter)
+	{
+		let gl = &self.gl;
+		unsafe { gl.edit_texture(glow::TEXTURE_CUBE_MAP, Some(self.texture)); }
+		filter.apply(gl, glow::TEXTURE_CUBE_MAP, self.mipmap, self.channel);
+	}
 }
@@ -2,6 +2,8 @@ use winit::{dpi::PhysicalSize, event::{ElementState, Event as RawEvent, Keyboard
 
 pub const DEBUG: bool = cfg!(debug_assertions);
 
+extern crate self as gru_opengl; //for the derive macros
+
 #[cfg(not(target_arch = "wasm32"))]
 mod desktop;
 #[cfg(not(target_arch = "wasm32"))]
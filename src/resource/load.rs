@@ -6,13 +6,31 @@ use rodio::{Decoder, source::Source};
 use super::*;
 use std::collections::hash_map::Entry;
 
-impl<T: AttributesReprCpacked> Load for Shader<T> {
-    type Config = ();
+//#include "name" is loaded relative to the including file
+#[derive(Default)]
+pub struct ShaderLoadConfig {
+    pub includes: Vec<&'static str>, //overrides, #include "name" is loaded from shaders/name in every file
+    pub defines: Vec<(&'static str, &'static str)>,
+}
+
+//path relative to the shaders folder as used by GlslPreprocessor::include_file
+fn shader_path(lp: &Loadprotocol, keyname: &str) -> String {
+    let path = lp.get_path(keyname).replace('\\', "/");
+    path.strip_prefix("shaders/").unwrap_or(&path).to_string()
+}
+
+impl<T: VertexStreams> Load for Shader<T> {
+    type Config = ShaderLoadConfig;
     fn path(file_name: &'static str) -> PathBuf {
         PathBuf::from("shaders").join(file_name) //no extension because 2 files .vert .frag in function
     }
 
-    fn load(key_gen: &mut Id<u64>, path: &PathBuf, ctx: &mut Context) -> Loadprotocol {
+    fn load(
+        key_gen: &mut Id<u64>,
+        path: &PathBuf,
+        ctx: &mut Context,
+        config: &Self::Config,
+    ) -> Loadprotocol {
         let mut lp = Loadprotocol::empty(format!("Shader {path:?}"));
         lp.request_file(
             key_gen,
@@ -26,60 +44,187 @@ impl<T: AttributesReprCpacked> Load for Shader<T> {
             "frag",
             ctx,
         );
+        for include in &config.includes {
+            lp.request_file(
+                key_gen,
+                &PathBuf::from("shaders").join(include).to_string_lossy(),
+                &format!("include {include}"),
+                ctx,
+            );
+        }
         lp
     }
 
-    fn interpret(lp: &Loadprotocol, gl: &mut Gl, _: &mut Self::Config) -> Self {
+    fn resolve(
+        lp: &mut Loadprotocol,
+        keyname: &str,
+        key_gen: &mut Id<u64>,
+        ctx: &mut Context,
+        config: &Self::Config,
+    ) {
+        let path = shader_path(lp, keyname);
+        let glsl = String::from_utf8_lossy(lp.get_data(keyname)).into_owned();
+        for name in GlslPreprocessor::include_names(&glsl) {
+            if config.includes.iter().any(|include| *include == name) {
+                continue;
+            }
+            let include = GlslPreprocessor::include_path(&path, name);
+            let keyname = format!("file {include}");
+            if !lp.requested(&keyname) {
+                lp.request_file(key_gen, &format!("shaders/{include}"), &keyname, ctx);
+            }
+        }
+    }
+
+    fn interpret(lp: &Loadprotocol, gl: &mut Gl, config: &mut Self::Config) -> Result<Self, String> {
+        let mut preprocessor = GlslPreprocessor::new();
+        preprocessor.source_path(&shader_path(lp, "vert"));
+        for include in &config.includes {
+            let glsl = String::from_utf8_lossy(lp.get_data(&format!("include {include}")));
+            preprocessor.include(include, &glsl);
+        }
+        for keyname in lp.keynames() {
+            if let Some(include) = keyname.strip_prefix("file ") {
+                preprocessor.include_file(include, &String::from_utf8_lossy(lp.get_data(keyname)));
+            }
+        }
+        for (name, value) in &config.defines {
+            preprocessor.define(name, value);
+        }
         let vertex_glsl = String::from_utf8_lossy(lp.get_data("vert"));
         let fragment_glsl = String::from_utf8_lossy(lp.get_data("frag"));
-        gl.new_shader(&vertex_glsl, &fragment_glsl)
+        gl.try_new_shader_with(&preprocessor, &vertex_glsl, &fragment_glsl)
+            .map_err(|err| err.to_string())
     }
 }
 
 pub struct TextureLoadConfig {
     pub channel: crate::gl::TextureChannel,
     pub mipmap: bool,
+    pub filter: crate::gl::TextureFilter,
     pub wrap_s: crate::gl::TextureWrap,
     pub wrap_t: crate::gl::TextureWrap,
 }
 
 impl TextureLoadConfig {
-    fn add_size(&self, size: u32) -> TextureConfig {
+    fn add_size(&self, width: u32, height: u32) -> TextureConfig {
         TextureConfig {
-            size,
+            width,
+            height,
             channel: self.channel,
             mipmap: self.mipmap,
+            filter: self.filter,
             wrap_s: self.wrap_s,
             wrap_t: self.wrap_t,
         }
     }
 }
 
-impl<const P: bool> Load for Texture<P> {
+impl Load for Texture {
     type Config = TextureLoadConfig;
     fn path(file_name: &'static str) -> PathBuf {
         PathBuf::from("textures").join(file_name).with_extension("png")
     }
 
-    fn load(key_gen: &mut Id<u64>, path: &PathBuf, ctx: &mut Context) -> Loadprotocol {
+    fn load(
+        key_gen: &mut Id<u64>,
+        path: &PathBuf,
+        ctx: &mut Context,
+        _: &Self::Config,
+    ) -> Loadprotocol {
         let mut lp = Loadprotocol::empty(format!("Texture {path:?}"));
         lp.request_file(key_gen, &path.to_string_lossy(), "file", ctx);
         lp
     }
 
-    fn interpret(lp: &Loadprotocol, gl: &mut Gl, config: &mut Self::Config) -> Self {
-        let name = lp.name();
-        let img = image::load_from_memory(lp.get_data("file")).unwrap();
+    fn interpret(lp: &Loadprotocol, gl: &mut Gl, config: &mut Self::Config) -> Result<Self, String> {
+        let img = image::load_from_memory(lp.get_data("file"))
+            .map_err(|err| format!("{}: {err}", lp.name()))?;
         let (width, height) = img.dimensions();
-        if width != height {
-            panic!("Texture {name} is not quadratic (w/h) = ({width}/{height})")
-        };
-        let img = match config.channel {
-            TextureChannel::RGBA => img.into_rgba8().into_raw(),
-            TextureChannel::RGB => img.into_rgb8().into_raw(),
-            TextureChannel::A => img.into_luma8().into_raw(),
-        };
-        gl.new_texture(&config.add_size(width), &img)
+        let img = decode_image(&lp.name(), img, config.channel)?;
+        Ok(gl.new_texture(&config.add_size(width, height), &img))
+    }
+}
+
+fn decode_image(
+    name: &str,
+    img: image::DynamicImage,
+    channel: TextureChannel,
+) -> Result<Vec<u8>, String> {
+    match channel {
+        TextureChannel::RGBA => Ok(img.into_rgba8().into_raw()),
+        TextureChannel::RGB => Ok(img.into_rgb8().into_raw()),
+        TextureChannel::A => Ok(img.into_luma8().into_raw()),
+        TextureChannel::Depth | TextureChannel::DepthStencil => {
+            Err(format!("{name} can not be loaded into a depth texture"))
+        }
+        TextureChannel::RGBFloat
+        | TextureChannel::RGBAFloat
+        | TextureChannel::RGBHalf
+        | TextureChannel::RGBAHalf => Err(format!("{name} can not be loaded into a float texture")),
+    }
+}
+
+pub struct CubeTextureLoadConfig {
+    pub channel: crate::gl::TextureChannel,
+    pub mipmap: bool,
+    pub filter: crate::gl::TextureFilter,
+}
+
+impl CubeTextureLoadConfig {
+    fn add_size(&self, size: u32) -> CubeTextureConfig {
+        CubeTextureConfig {
+            size,
+            channel: self.channel,
+            mipmap: self.mipmap,
+            filter: self.filter,
+        }
+    }
+}
+
+//in the order +X, -X, +Y, -Y, +Z, -Z
+const CUBE_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+impl Load for CubeTexture {
+    type Config = CubeTextureLoadConfig;
+    fn path(file_name: &'static str) -> PathBuf {
+        PathBuf::from("textures").join(file_name) //folder with the 6 faces px.png, nx.png, ...
+    }
+
+    fn load(
+        key_gen: &mut Id<u64>,
+        path: &PathBuf,
+        ctx: &mut Context,
+        _: &Self::Config,
+    ) -> Loadprotocol {
+        let mut lp = Loadprotocol::empty(format!("CubeTexture {path:?}"));
+        for face in CUBE_FACES {
+            lp.request_file(
+                key_gen,
+                &path.join(face).with_extension("png").to_string_lossy(),
+                face,
+                ctx,
+            );
+        }
+        lp
+    }
+
+    fn interpret(lp: &Loadprotocol, gl: &mut Gl, config: &mut Self::Config) -> Result<Self, String> {
+        let name = lp.name();
+        let mut faces = Vec::with_capacity(CUBE_FACES.len());
+        for face in CUBE_FACES {
+            let img = image::load_from_memory(lp.get_data(face))
+                .map_err(|err| format!("{name} ({face}): {err}"))?;
+            faces.push((img.dimensions(), decode_image(&name, img, config.channel)?));
+        }
+        let (width, height) = faces[0].0;
+        if width != height || faces.iter().any(|(dims, _)| *dims != (width, height)) {
+            return Err(format!("{name} faces are not quadratic and of equal size"));
+        }
+        Ok(gl.new_cube_texture(
+            &config.add_size(width),
+            std::array::from_fn(|face| &faces[face].1[..]),
+        ))
     }
 }
 
@@ -95,18 +240,23 @@ pub trait BuildFromGltf: AttributesReprCpacked {
     fn build(data: VertexData) -> Self;
 }
 
-pub struct Model<V: BuildFromGltf> {
+pub struct Model<V: BuildFromGltf, I: IndexType = u16> {
     pub vertices: VertexBuffer<V>,
-    pub indices: IndexBuffer,
+    pub indices: IndexBuffer<I>,
 }
 
-impl<V: BuildFromGltf> Load for Model<V> {
+impl<V: BuildFromGltf, I: IndexType> Load for Model<V, I> {
     type Config = ();
     fn path(file_name: &'static str) -> PathBuf {
         PathBuf::from("models").join(file_name)
     }
 
-    fn load(key_gen: &mut Id<u64>, path: &PathBuf, ctx: &mut Context) -> Loadprotocol {
+    fn load(
+        key_gen: &mut Id<u64>,
+        path: &PathBuf,
+        ctx: &mut Context,
+        _: &Self::Config,
+    ) -> Loadprotocol {
         let mut lp = Loadprotocol::empty(format!("Model {path:?}"));
         lp.request_file(
             key_gen,
@@ -123,9 +273,11 @@ impl<V: BuildFromGltf> Load for Model<V> {
         lp
     }
 
-    fn interpret(lp: &Loadprotocol, gl: &mut Gl, _: &mut Self::Config) -> Self {
+    fn interpret(lp: &Loadprotocol, gl: &mut Gl, _: &mut Self::Config) -> Result<Self, String> {
         let model_name = lp.name();
-        let doc = Gltf::from_slice(lp.get_data("gltf")).unwrap().document;
+        let doc = Gltf::from_slice(lp.get_data("gltf"))
+            .map_err(|err| format!("Model {model_name:?}: {err}"))?
+            .document;
         let mut bin = AHashMap::new();
         for buffer in doc.buffers() {
             match buffer.source() {
@@ -134,26 +286,39 @@ impl<V: BuildFromGltf> Load for Model<V> {
                         vac.insert(lp.get_data("bin"));
                     }
                 }
-                buffer::Source::Bin => unreachable!(),
+                buffer::Source::Bin => {
+                    return Err(format!("Model {model_name:?} has an embedded buffer"));
+                }
             }
         }
         let mut meshes = doc.meshes();
-        let mesh = meshes.next().unwrap();
+        let mesh = meshes
+            .next()
+            .ok_or_else(|| format!("Model {model_name:?} has no mesh"))?;
         if meshes.next().is_some() {
-            panic!("Model {model_name:?} has more than 1 mesh");
+            return Err(format!("Model {model_name:?} has more than 1 mesh"));
         }
-        let name = mesh.name().unwrap().to_string();
+        let name = mesh
+            .name()
+            .ok_or_else(|| format!("Model {model_name:?} has a mesh without name"))?
+            .to_string();
         let mut indices = Vec::new();
 
         let mut primitives = mesh.primitives();
-        let primitive = primitives.next().unwrap();
+        let primitive = primitives
+            .next()
+            .ok_or_else(|| format!("Mesh {name} has no primitive"))?;
         if primitives.next().is_some() {
-            panic!("Mesh {name} has more than 1 primitive");
+            return Err(format!("Mesh {name} has more than 1 primitive"));
         }
 
         let i0 = 0; //TODO dafuq means old code ->> = (positions.len() / 3) as u16;
-        let accessor = primitive.indices().unwrap();
-        let view = accessor.view().unwrap();
+        let accessor = primitive
+            .indices()
+            .ok_or_else(|| format!("Mesh {name} has no indices"))?;
+        let view = accessor
+            .view()
+            .ok_or_else(|| format!("Mesh {name} has sparse indices"))?;
         let data = &(if let buffer::Source::Uri(name) = view.buffer().source() {
             bin.get(name).unwrap()
         } else {
@@ -162,21 +327,38 @@ impl<V: BuildFromGltf> Load for Model<V> {
         let stride = view.stride().unwrap_or_else(|| accessor.size());
         for i in 0..accessor.count() {
             let start = (stride * i) + accessor.offset();
-            let data = &data[start..(start + accessor.size())];
+            let data = data
+                .get(start..(start + accessor.size()))
+                .ok_or_else(|| format!("Mesh {name} has indices outside of its buffer"))?;
             match accessor.data_type() {
+                accessor::DataType::U8 => {
+                    for int in data {
+                        indices.push(*int as u32 + i0);
+                    }
+                }
                 accessor::DataType::U16 => {
                     for int in data.chunks_exact(2) {
-                        indices.push(u16::from_ne_bytes(int.try_into().unwrap()) + i0);
+                        indices.push(u16::from_ne_bytes(int.try_into().unwrap()) as u32 + i0);
                     }
                 }
                 accessor::DataType::U32 => {
                     for int in data.chunks_exact(4) {
-                        indices.push(u32::from_ne_bytes(int.try_into().unwrap()) as u16 + i0);
+                        indices.push(u32::from_ne_bytes(int.try_into().unwrap()) + i0);
                     }
                 }
-                _ => unreachable!(),
+                data_type => {
+                    return Err(format!("Mesh {name} has indices of type {data_type:?}"));
+                }
             }
         }
+        let indices: Vec<I> = indices
+            .into_iter()
+            .map(|index| {
+                I::from_u32(index).ok_or_else(|| {
+                    format!("Mesh {name} has the index {index} which does not fit the index type")
+                })
+            })
+            .collect::<Result<_, _>>()?;
 
         let mut positions = Vec::new();
         let mut normals = Vec::new();
@@ -199,9 +381,11 @@ impl<V: BuildFromGltf> Load for Model<V> {
             } {
                 let accessor = attribute.1;
                 if accessor.data_type() != accessor::DataType::F32 {
-                    panic!("ModelData::new: Model \"{model_name}\" contains not F32 data for {:?} (instead {:?}).", attribute.0, accessor.data_type());
+                    return Err(format!("Model {model_name:?} contains not F32 data for {:?} (instead {:?})", attribute.0, accessor.data_type()));
                 }
-                let view = accessor.view().unwrap();
+                let view = accessor
+                    .view()
+                    .ok_or_else(|| format!("Mesh {name} has sparse {:?}", attribute.0))?;
                 let data = &(if let buffer::Source::Uri(name) = view.buffer().source() {
                     bin.get(name).unwrap()
                 } else {
@@ -210,7 +394,9 @@ impl<V: BuildFromGltf> Load for Model<V> {
                 let stride = view.stride().unwrap_or_else(|| accessor.size());
                 for i in 0..accessor.count() {
                     let start = (stride * i) + accessor.offset();
-                    let data = &data[start..(start + accessor.size())];
+                    let data = data
+                        .get(start..(start + accessor.size()))
+                        .ok_or_else(|| format!("Mesh {name} has {:?} outside of its buffer", attribute.0))?;
                     for float in data.chunks_exact(4) {
                         vec.push(f32::from_le_bytes(float.try_into().unwrap()));
                     }
@@ -246,13 +432,15 @@ impl<V: BuildFromGltf> Load for Model<V> {
             }));
         }
         let mut vert_buffer = gl.new_vertex_buffer(vertices.len() as u32, BufferAccess::Static);
-        let mut index_buffer = gl.new_index_buffer(indices.len() as u32, BufferAccess::Static);
+        let mut index_buffer = gl
+            .try_new_index_buffer(indices.len() as u32, BufferAccess::Static)
+            .map_err(|err| format!("Model {model_name:?}: {err}"))?;
         vert_buffer.data(0, &vertices);
         index_buffer.data(0, &indices);
-        Model::<V> {
+        Ok(Model::<V, I> {
             vertices: vert_buffer,
             indices: index_buffer,
-        }
+        })
     }
 }
 
@@ -276,20 +464,20 @@ pub struct AudioSource
 impl Load for Audio 
 {
     type Config = ();
-    fn load(key_gen: &mut Id<u64>, file_path: &std::path::PathBuf, ctx: &mut Context) -> Loadprotocol 
+    fn load(key_gen: &mut Id<u64>, file_path: &std::path::PathBuf, ctx: &mut Context, _: &Self::Config) -> Loadprotocol 
     {
         let mut lp = Loadprotocol::empty(format!("Sound {file_path:?}"));
         lp.request_file(key_gen, &file_path.to_string_lossy(), "file", ctx);
         lp
     }
 
-    fn interpret(lp: &Loadprotocol, _gl: &mut Gl, _: &mut Self::Config) -> Self 
+    fn interpret(lp: &Loadprotocol, _gl: &mut Gl, _: &mut Self::Config) -> Result<Self, String> 
     {
-        let decoder = Decoder::new_vorbis(std::io::Cursor::new(lp.get_data("file").to_owned())).unwrap(); //TODO to_owned necessary since rodio 0.15.0. avoidable?
+        let decoder = Decoder::new_vorbis(std::io::Cursor::new(lp.get_data("file").to_owned())).map_err(|err| format!("{}: {err}", lp.name()))?; //TODO to_owned necessary since rodio 0.15.0. avoidable?
         let channels = decoder.channels();
         let sample_rate = decoder.sample_rate();
         let data = decoder.convert_samples::<f32>().collect::<Vec<_>>();
-        Audio::new(channels, sample_rate, data)
+        Ok(Audio::new(channels, sample_rate, data))
     }
 
     fn path(file_name: &'static str) -> std::path::PathBuf 
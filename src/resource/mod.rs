@@ -71,8 +71,13 @@ impl<T: ResourceSystem> ResSys<T> {
         )
     }
 
-    pub fn add_file_event(&mut self, file: File, gl: &mut Gl) {
-        self.add_file_event_wrapper(file, gl)
+    pub fn add_file_event(&mut self, file: File, ctx: &mut Context) {
+        self.res
+            .get_iter_mut()
+            .find(|rl| rl.needs_key(&file.key))
+            .unwrap_or_else(|| panic!("Unknown key recived {:?} for File {:?}", file.key, file.path))
+            .add_file(file, &mut self.load_id, ctx);
+        self.loaded_counter.increase();
     }
 
     pub fn start_loading(&mut self, ctx: &mut Context) {
@@ -87,10 +92,6 @@ impl<T: ResourceSystem> ResourceSystemWrapper for ResSys<T> {
     }
     */
 
-    fn loaded_counter(&mut self) -> &mut Id<u64> {
-        &mut self.loaded_counter
-    }
-
     fn empty(id: u64) -> Self {
         Self {
             res: T::empty(),
@@ -100,10 +101,6 @@ impl<T: ResourceSystem> ResourceSystemWrapper for ResSys<T> {
         }
     }
 
-    fn get_iter_mut(&mut self) -> ResIterMut {
-        self.res.get_iter_mut()
-    }
-
     fn get_iter(&self) -> ResIter {
         self.res.get_iter()
     }
@@ -130,10 +127,8 @@ impl<T: ResourceSystem> std::ops::DerefMut for ResSys<T> {
 
 trait ResourceSystemWrapper: std::ops::Deref + Sized {
     //fn load_id(&mut self) -> &mut Id<u64>;
-    fn loaded_counter(&mut self) -> &mut Id<u64>;
 
     fn empty(id: u64) -> Self;
-    fn get_iter_mut(&mut self) -> ResIterMut;
     fn get_iter(& self) -> ResIter;
 
     fn load(&mut self, ctx: &mut Context);
@@ -148,14 +143,6 @@ trait ResourceSystemWrapper: std::ops::Deref + Sized {
     fn create() -> Self {
         Self::empty(next_resource_id())
     }
-
-    fn add_file_event_wrapper(&mut self, file: File, gl: &mut Gl) {
-        self.get_iter_mut()
-            .find(|rl| rl.needs_key(&file.key))
-            .unwrap_or_else(|| panic!("Unknown key recived {:?} for File {:?}", file.key, file.path))
-            .add_file(file, gl);
-        self.loaded_counter().increase();
-    }
 }
 
 pub trait ResourceSystem: Sized {
@@ -205,16 +192,31 @@ macro_rules! impl_ResourceSystem {
     };
 }
 
-pub trait Load {
+pub trait Load: Sized {
     type Config;
-    fn load(key_gen: &mut Id<u64>, file_path: &PathBuf, ctx: &mut Context) -> Loadprotocol;
-    fn interpret(lp: &Loadprotocol, gl: &mut Gl, config: &mut Self::Config) -> Self;
+    fn load(
+        key_gen: &mut Id<u64>,
+        file_path: &PathBuf,
+        ctx: &mut Context,
+        config: &Self::Config,
+    ) -> Loadprotocol;
+    //called for every file as it arrives, can request further files (like shader includes)
+    fn resolve(
+        _lp: &mut Loadprotocol,
+        _keyname: &str,
+        _key_gen: &mut Id<u64>,
+        _ctx: &mut Context,
+        _config: &Self::Config,
+    ) {
+    }
+    fn interpret(lp: &Loadprotocol, gl: &mut Gl, config: &mut Self::Config) -> Result<Self, String>;
     fn path(file_name: &'static str) -> PathBuf;
 }
 enum ResState<T> {
     Empty,
     Loading(Loadprotocol),
     Loaded(T),
+    Failed(String),
 }
 
 impl<T: Load> ResState<T> {
@@ -223,6 +225,7 @@ impl<T: Load> ResState<T> {
             ResState::Empty => None,
             ResState::Loading(_) => None,
             ResState::Loaded(res) => Some(res),
+            ResState::Failed(_) => None,
         }
     }
 }
@@ -233,6 +236,7 @@ impl<T: Load> Display for ResState<T> {
             ResState::Empty => write!(f, "Empty"),
             ResState::Loading(_) => write!(f, "Loading"),
             ResState::Loaded(_) => write!(f, "Loaded"),
+            ResState::Failed(err) => write!(f, "Failed ({err})"),
         }
     }
 }
@@ -246,21 +250,29 @@ pub trait ResLoad {
     fn load(&mut self, key_gen: &mut Id<u64>, ctx: &mut Context);
     fn interpret(&mut self, gl: &mut Gl);
     fn needs_key(&self, key: &u64) -> bool;
-    fn add_file(&mut self, file: File, gl: &mut Gl);
+    fn add_file(&mut self, file: File, key_gen: &mut Id<u64>, ctx: &mut Context);
     fn display_string(&self) -> String;
 }
 
 impl<T: 'static + Load> ResLoad for Res<T> {
     fn load(&mut self, key_gen: &mut Id<u64>, ctx: &mut Context) {
         log(&format!("Start loading {:?}", self.path));
-        self.res = ResState::Loading(T::load(key_gen, &self.path, ctx));
+        self.res = ResState::Loading(T::load(key_gen, &self.path, ctx, &self.config));
     }
 
     fn interpret(&mut self, gl: &mut Gl) {
         if let ResState::Loading(lp) = &self.res {
             let name = &lp.name();
-            self.res = ResState::Loaded(T::interpret(lp, gl, &mut self.config));
-            log(&format!("Loaded {name}"));
+            self.res = match T::interpret(lp, gl, &mut self.config) {
+                Ok(res) => {
+                    log(&format!("Loaded {name}"));
+                    ResState::Loaded(res)
+                }
+                Err(err) => {
+                    log(&format!("Failed to load {name}: {err}"));
+                    ResState::Failed(err)
+                }
+            };
         }
     }
 
@@ -268,8 +280,8 @@ impl<T: 'static + Load> ResLoad for Res<T> {
         self.needs_key(key)
     }
 
-    fn add_file(&mut self, file: File, gl: &mut Gl) {
-        self.add_file(file, gl);
+    fn add_file(&mut self, file: File, key_gen: &mut Id<u64>, ctx: &mut Context) {
+        self.add_file(file, key_gen, ctx);
     }
 
     fn display_string(&self) -> String {
@@ -279,9 +291,24 @@ impl<T: 'static + Load> ResLoad for Res<T> {
 
 impl<T: 'static + Load> Res<T> {
     pub fn get(&self) -> &T {
-        self.res
-            .get()
-            .unwrap_or_else(|| panic!("Resource not loaded {:?}", self.path))
+        match &self.res {
+            ResState::Failed(err) => panic!("Resource failed to load {:?}: {err}", self.path),
+            res => res
+                .get()
+                .unwrap_or_else(|| panic!("Resource not loaded {:?}", self.path)),
+        }
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        self.res.get()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        if let ResState::Failed(err) = &self.res {
+            Some(err)
+        } else {
+            None
+        }
     }
 
     pub fn get_config(&self) -> &T::Config {
@@ -320,14 +347,15 @@ impl<T: 'static + Load> Res<T> {
         }
     }
 
-    fn add_file(&mut self, file: File, gl: &mut Gl) {
+    fn add_file(&mut self, file: File, key_gen: &mut Id<u64>, ctx: &mut Context) {
         let mut complete = false;
         if let ResState::Loading(lp) = &mut self.res {
-            lp.add_file(file);
+            let keyname = lp.add_file(file);
+            T::resolve(lp, &keyname, key_gen, ctx, &self.config);
             complete = lp.can_be_interpreted();
         }
         if complete {
-            self.interpret(gl);
+            self.interpret(ctx.gl());
         }
     }
 }
@@ -345,10 +373,11 @@ pub struct Loadprotocol {
 }
 
 impl Loadprotocol {
-    fn add_file(&mut self, file: File) {
-        let keyname = self.keys.get(&file.key).unwrap();
+    fn add_file(&mut self, file: File) -> String {
+        let keyname = self.keys.get(&file.key).unwrap().clone();
         self.files.insert(keyname.clone(), file);
         self.missing_files -= 1;
+        keyname
     }
 
     fn can_be_interpreted(&self) -> bool {
@@ -359,6 +388,19 @@ impl Loadprotocol {
         &self.files.get(keyname).unwrap().data
     }
 
+    pub fn get_path(&self, keyname: &str) -> &str {
+        &self.files.get(keyname).unwrap().path
+    }
+
+    //keynames of the files that arrived so far
+    pub fn keynames(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(|keyname| keyname.as_str())
+    }
+
+    pub fn requested(&self, keyname: &str) -> bool {
+        self.keys.values().any(|requested| requested == keyname)
+    }
+
     pub fn empty(name: String) -> Self {
         let missing_files = 0;
         Loadprotocol {
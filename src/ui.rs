@@ -62,33 +62,28 @@ const FRAG: &str =
     }
 ";
 
+#[derive(AttributesReprCpacked)]
 #[repr(C, packed)]
 struct Vertex
 {
+    #[attribute(name = "in_pos")]
     pos: (f32, f32),
+    #[attribute(name = "in_coords")]
     coords: (f32, f32),
+    #[attribute(name = "in_color")]
     color: (f32, f32, f32, f32)
 }
 
-impl AttributesReprCpacked for Vertex
-{
-    const ATTRIBUTES: &'static [(BufferType, &'static str)] =
-    &[
-        (BufferType::Float { size: 2 }, "in_pos"),
-        (BufferType::Float { size: 2 }, "in_coords"),
-        (BufferType::Float { size: 4 }, "in_color")
-    ];
-}
-
 pub struct Binding
 {
     pos: Vec2,
     events: Vec<HardwareEvent>,
     shader: Shader<Vertex>,
-    tex_key: UniformKey<Texture<false>>,
-    glyphs: Option<(u64, Texture<false>)>,
-    vertices: VertexBuffer<Vertex>,
-    indices: IndexBuffer,
+    tex_key: UniformKey<Texture>,
+    glyphs: Option<(u64, Texture)>,
+    vertices: StreamBuffer<Vertex>,
+    indices: IndexStreamBuffer,
+    offset: u32,
     count: u32
 }
 
@@ -96,10 +91,7 @@ impl Binding
 {
     pub fn new(gl: &mut Gl) -> Self
     {
-        #[cfg(not(target_arch = "wasm32"))]
         let shader = gl.new_shader(VERT, FRAG);
-        #[cfg(target_arch = "wasm32")]
-        let shader = gl.new_shader(VERT, &format!("    #extension GL_OES_standard_derivatives : require\n{FRAG}"));
         let tex_key = shader.get_key("glyphs");
         Self
         {
@@ -108,8 +100,9 @@ impl Binding
             shader,
             tex_key,
             glyphs: None,
-            vertices: gl.new_vertex_buffer(0, BufferAccess::Dynamic),
-            indices: gl.new_index_buffer(0, BufferAccess::Dynamic),
+            vertices: gl.new_stream_buffer(0),
+            indices: gl.new_index_stream_buffer(0),
+            offset: 0,
             count: 0
         }
     }
@@ -157,9 +150,20 @@ impl Binding
         if self.glyphs.as_ref().map(|(version, _)| *version != font_version).unwrap_or(true)
         {
             if font_data.len() > 1 { unimplemented!("Multiple glyph textures ({}).", font_data.len()); }
-            let config = TextureConfig { size: TEXTURE_SIZE, channel: TextureChannel::A, mipmap: false, wrap_s: TextureWrap::Clamp, wrap_t: TextureWrap::Clamp };
-            let texture = gl.new_texture(&config, &font_data[0]);
-            self.glyphs = Some((font_version, texture));
+            match &mut self.glyphs
+            {
+                Some((version, texture)) =>
+                {
+                    texture.update_region(0, 0, TEXTURE_SIZE, TEXTURE_SIZE, &font_data[0]);
+                    *version = font_version;
+                },
+                None =>
+                {
+                    let config = TextureConfig { width: TEXTURE_SIZE, height: TEXTURE_SIZE, channel: TextureChannel::A, mipmap: false, filter: TextureFilter::LINEAR, wrap_s: TextureWrap::Clamp, wrap_t: TextureWrap::Clamp };
+                    let texture = gl.new_texture(&config, &font_data[0]);
+                    self.glyphs = Some((font_version, texture));
+                }
+            }
         }
 
         if new
@@ -184,10 +188,8 @@ impl Binding
                     color: vertex.color.to_normalized_srgb()
                 }
             }).collect();
-            if self.vertices.len() < vertices.len() as u32 { self.vertices = gl.new_vertex_buffer(vertices.len() as u32, BufferAccess::Dynamic); }
-            if self.indices.len() < indices.len() as u32 { self.indices = gl.new_index_buffer(indices.len() as u32, BufferAccess::Dynamic); }
-            self.vertices.data(0, &vertices);
-            self.indices.data(0, &indices);
+            let base_vertex = self.vertices.push_indexed::<u16>(&vertices);
+            self.offset = self.indices.push(&indices, base_vertex);
             self.count = indices.len() as u32;
         }
     }
@@ -197,9 +199,9 @@ impl Binding
         if let Some((_, glyphs)) = &self.glyphs
         {
             rp
-                .pipeline(&self.shader, PipelineInfo { depth_test: false, alpha_blend: true, face_cull: true })
+                .pipeline(&self.shader, PipelineInfo { depth_test: false, blend: Some(BlendMode::Alpha), ..PipelineInfo::DEFAULT })
                 .uniform_key(&self.tex_key, glyphs)
-                .draw(Primitives::Triangles, &self.vertices, Some(&self.indices), 0, self.count);
+                .draw(Primitives::Triangles, &self.vertices, Some(&self.indices), self.offset, self.count);
         }
     }
 }